@@ -0,0 +1,48 @@
+// Cost of drawing one frame of the console against the size of the scrollback.
+//
+// Run with `cargo bench --bench frame`. Only the visible rows are laid out,
+// so the time per frame should stay flat as the number of lines grows.
+
+use std::time::Instant;
+
+use egui::{vec2, CentralPanel, Context, Pos2, RawInput, Rect};
+use egui_console::{ConsoleBuilder, ConsoleWindow};
+
+const FRAMES: u32 = 200;
+
+fn frame(ctx: &Context, console: &mut ConsoleWindow) {
+    let input = RawInput {
+        screen_rect: Some(Rect::from_min_size(Pos2::ZERO, vec2(1024.0, 768.0))),
+        ..Default::default()
+    };
+    let _ = ctx.run(input, |ctx| {
+        CentralPanel::default().show(ctx, |ui| {
+            console.draw(ui);
+        });
+    });
+}
+
+fn main() {
+    for lines in [1_000, 10_000, 100_000, 1_000_000] {
+        let ctx = Context::default();
+        let mut console = ConsoleBuilder::new().scrollback_size(lines).build();
+        for i in 0..lines {
+            console.write(&format!("\x1b[32mline {i}\x1b[0m some ordinary output"));
+        }
+        // warm up the font atlas
+        for _ in 0..10 {
+            frame(&ctx, &mut console);
+        }
+
+        let start = Instant::now();
+        for i in 0..FRAMES {
+            // new output every frame so the visible rows change
+            console.write(&format!("more output {i}"));
+            frame(&ctx, &mut console);
+        }
+        println!(
+            "{lines:>9} lines of scrollback: {:?} per frame",
+            start.elapsed() / FRAMES
+        );
+    }
+}
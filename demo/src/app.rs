@@ -1,8 +1,7 @@
 use crate::clap::syntax;
-use anyhow::Result;
 /// We derive Deserialize/Serialize so we can persist app state on shutdown.
 //use egui_console::console::{ConsoleBuilder, ConsoleEvent, ConsoleWindow};
-use egui_console::{ConsoleBuilder, ConsoleEvent, ConsoleWindow};
+use egui_console::{ClapCommands, ConsoleBuilder, ConsoleEvent, ConsoleWindow};
 #[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
 //#[derive(serde::Deserialize, serde::Serialize)]
 #[cfg_attr(feature = "persistence", serde(default))] // if we add new fields, give them default values when deserializing old state
@@ -13,6 +12,8 @@ pub struct ConsoleDemo {
     // This how you opt-out of serialization of a field
     value: f32,
     console_win: ConsoleWindow,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    commands: ClapCommands<egui::Context>,
 }
 
 impl Default for ConsoleDemo {
@@ -24,8 +25,10 @@ impl Default for ConsoleDemo {
             console_win: ConsoleBuilder::new()
                 .prompt(">> ")
                 .history_size(20)
+                .history_expansion(true)
                 .tab_quote_character('\"')
                 .build(),
+            commands: commands(),
         }
     }
 }
@@ -44,11 +47,7 @@ impl ConsoleDemo {
         } else {
             Self::default()
         };
-        for cmd in syntax().get_subcommands() {
-            app.console_win
-                .command_table_mut()
-                .push(cmd.get_name().to_string());
-        }
+        app.commands.install(&mut app.console_win);
 
         app
     }
@@ -102,24 +101,8 @@ impl eframe::App for ConsoleDemo {
                     console_response = self.console_win.draw(ui);
                 });
             if let ConsoleEvent::Command(command) = console_response {
-                let resp = match self.dispatch(&command, ctx) {
-                    Err(e) => {
-                        if let Some(original_error) = e.downcast_ref::<clap::error::Error>() {
-                            format!("{}", original_error)
-                        } else if e.backtrace().status()
-                            == std::backtrace::BacktraceStatus::Captured
-                        {
-                            format!("{} {}", e, e.backtrace())
-                        } else {
-                            format!("{}", e)
-                        }
-                    }
-
-                    Ok(string) => string, // continue
-                };
-                if !resp.is_empty() {
-                    self.console_win.write(&resp);
-                }
+                self.commands
+                    .dispatch(&command, &mut self.console_win, &mut ctx.clone());
                 self.console_win.prompt();
             }
 
@@ -130,69 +113,88 @@ impl eframe::App for ConsoleDemo {
         });
     }
 }
-impl ConsoleDemo {
-    pub fn dispatch(&mut self, line: &str, ctx: &egui::Context) -> Result<String> {
-        // let args = line.split_whitespace();
-        let args = shlex::split(line).ok_or(anyhow::anyhow!("cannot parse"))?;
-        // parse with clap
-        let matches = syntax().try_get_matches_from(args)?;
-        // execute the command
-        match matches.subcommand() {
-            Some(("cd", args)) => {
-                let dir = args.get_one::<String>("directory").unwrap();
-                std::env::set_current_dir(dir)?;
-                let cwd = std::env::current_dir()?;
-                Ok(format!("Current working directory: {}", cwd.display()))
-            }
-            Some(("dark", _)) => {
-                //  let ctx = egui::Context::default();
-                ctx.set_visuals(egui::Visuals::dark());
-                Ok("Dark mode enabled".to_string())
-            }
-            Some(("light", _)) => {
-                //   let ctx = egui::Context::default();
-                ctx.set_visuals(egui::Visuals::light());
-                Ok("Light mode enabled".to_string())
-            }
-            Some(("quit", _)) => {
-                //   let ctx = egui::Context::default();
-                ctx.send_viewport_cmd(egui::ViewportCommand::Close);
-                Ok("Bye".to_string())
-            }
-            Some(("clear_screen", _)) => {
-                self.console_win.clear();
-                Ok("".to_string())
-            }
-            Some(("dir", args)) => {
-                let filter = if let Some(filter) = args.get_one::<String>("filter") {
-                    filter.clone()
-                } else {
-                    "".to_string()
-                };
-                let entries = std::fs::read_dir(".")?;
-                let mut result = String::new();
-                for entry in entries {
-                    let entry = entry?;
-                    let path = entry.path();
-                    if path.display().to_string().contains(filter.as_str()) {
-                        result.push_str(&format!("{}\n", path.display()));
+// the handlers for the commands defined in `syntax`
+fn commands() -> ClapCommands<egui::Context> {
+    ClapCommands::<egui::Context>::new(syntax())
+        .handler("cd", |args, ctx| {
+            let dir = args.get_one::<String>("directory").unwrap();
+            std::env::set_current_dir(dir)?;
+            let cwd = std::env::current_dir()?;
+            ctx.console
+                .write(&format!("Current working directory: {}", cwd.display()));
+            // commands remember the directory they were run in
+            ctx.console.set_history_tag(Some(cwd.display().to_string()));
+            Ok(())
+        })
+        .handler("dark", |_, ctx| {
+            ctx.state.set_visuals(egui::Visuals::dark());
+            ctx.console.write("Dark mode enabled");
+            Ok(())
+        })
+        .handler("light", |_, ctx| {
+            ctx.state.set_visuals(egui::Visuals::light());
+            ctx.console.write("Light mode enabled");
+            Ok(())
+        })
+        .handler("quit", |_, ctx| {
+            ctx.state.send_viewport_cmd(egui::ViewportCommand::Close);
+            ctx.console.write("Bye");
+            Ok(())
+        })
+        .handler("clear_screen", |_, ctx| {
+            ctx.console.clear();
+            Ok(())
+        })
+        .handler("dir", |args, ctx| {
+            let filter = if let Some(filter) = args.get_one::<String>("filter") {
+                filter.clone()
+            } else {
+                "".to_string()
+            };
+            let entries = std::fs::read_dir(".")?;
+            for entry in entries {
+                let entry = entry?;
+                let path = entry.path();
+                if path.display().to_string().contains(filter.as_str()) {
+                    // directories in blue, like ls --color
+                    if path.is_dir() {
+                        ctx.console
+                            .write(&format!("\x1b[1;34m{}\x1b[0m", path.display()));
+                    } else {
+                        ctx.console.write(&format!("{}", path.display()));
                     }
                 }
-                Ok(result)
             }
-            Some(("history", _)) => {
-                let history = self.console_win.get_history();
-                let mut result = String::new();
-                for (i, line) in history.iter().enumerate() {
-                    result.push_str(&format!("{}: {}\n", i, line));
+            Ok(())
+        })
+        .handler("history", |_, ctx| {
+            let history = ctx.console.history_entries().clone();
+            for (i, entry) in history.iter().enumerate() {
+                // the time of day (UTC), how long it took and whether it worked
+                let time = entry.timestamp.map_or(String::new(), |t| {
+                    format!("{:02}:{:02}:{:02}", t / 3600 % 24, t / 60 % 60, t % 60)
+                });
+                let duration = entry
+                    .duration
+                    .map_or(String::new(), |d| format!("{:.0?}", d));
+                let status = match entry.success {
+                    Some(true) => "ok",
+                    Some(false) => "failed",
+                    None => "",
+                };
+                let mut line = format!(
+                    "{:>3}: {:8} {:>6} {:6} {}",
+                    i, time, duration, status, entry.command
+                );
+                if let Some(tag) = &entry.tag {
+                    line.push_str(&format!("  [{}]", tag));
                 }
-                Ok(result)
-            }
-            Some(("clear_history", _)) => {
-                self.console_win.clear_history();
-                Ok("".to_string())
+                ctx.console.write(&line);
             }
-            _ => Ok("Unknown command".to_string()),
-        }
-    }
+            Ok(())
+        })
+        .handler("clear_history", |_, ctx| {
+            ctx.console.clear_history();
+            Ok(())
+        })
 }
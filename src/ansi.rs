@@ -0,0 +1,233 @@
+use std::ops::Range;
+
+use egui::Color32;
+
+use crate::style::SpanStyle;
+
+// standard xterm palette for the 16 basic colours
+const BASIC_COLORS: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+const CUBE_STEPS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn palette_color(idx: u8) -> Color32 {
+    match idx {
+        0..=15 => {
+            let (r, g, b) = BASIC_COLORS[idx as usize];
+            Color32::from_rgb(r, g, b)
+        }
+        16..=231 => {
+            let i = idx - 16;
+            Color32::from_rgb(
+                CUBE_STEPS[(i / 36) as usize],
+                CUBE_STEPS[((i / 6) % 6) as usize],
+                CUBE_STEPS[(i % 6) as usize],
+            )
+        }
+        232..=255 => {
+            let level = 8 + 10 * (idx - 232);
+            Color32::from_rgb(level, level, level)
+        }
+    }
+}
+
+/// Strips ANSI escape sequences out of output text, turning SGR sequences
+/// into styled ranges.
+///
+/// The current style carries over from one call to the next just like a
+/// terminal, so a colour set in one write applies until it is reset.
+#[derive(Debug, Default)]
+pub(crate) struct AnsiParser {
+    style: SpanStyle,
+}
+
+impl AnsiParser {
+    /// Append the printable part of `input` to `out`.
+    /// Returns the styled byte ranges (relative to `out`) of the appended text.
+    /// Unstyled text does not get a range.
+    pub(crate) fn parse(
+        &mut self,
+        input: &str,
+        out: &mut String,
+    ) -> Vec<(Range<usize>, SpanStyle)> {
+        let mut ranges: Vec<(Range<usize>, SpanStyle)> = Vec::new();
+        let mut chars = input.char_indices().peekable();
+        let mut run_start = 0;
+
+        while let Some((idx, ch)) = chars.next() {
+            if ch != '\x1b' {
+                continue;
+            }
+            push_run(&mut ranges, out, &input[run_start..idx], self.style);
+            match chars.next() {
+                // CSI - parameters, intermediates, then a final byte
+                Some((_, '[')) => {
+                    let params_start = idx + 2;
+                    let mut params_end = input.len();
+                    let mut final_byte = None;
+                    for (i, c) in chars.by_ref() {
+                        if ('\x40'..='\x7e').contains(&c) {
+                            params_end = i;
+                            final_byte = Some(c);
+                            break;
+                        }
+                    }
+                    if final_byte == Some('m') {
+                        self.apply_sgr(&input[params_start..params_end]);
+                    }
+                }
+                // OSC - runs until BEL or ST (ESC \)
+                Some((_, ']')) => {
+                    while let Some((_, c)) = chars.next() {
+                        if c == '\x07' {
+                            break;
+                        }
+                        if c == '\x1b' {
+                            chars.next_if(|&(_, c)| c == '\\');
+                            break;
+                        }
+                    }
+                }
+                // anything else is a two character sequence we ignore
+                _ => {}
+            }
+            run_start = chars.peek().map_or(input.len(), |&(i, _)| i);
+        }
+        push_run(&mut ranges, out, &input[run_start..], self.style);
+        ranges
+    }
+
+    fn apply_sgr(&mut self, params: &str) {
+        // ':' is the official sub parameter separator, ';' is what everybody uses
+        let mut codes = params.split([';', ':']).map(|p| {
+            if p.is_empty() {
+                0
+            } else {
+                p.parse::<u16>().unwrap_or(u16::MAX)
+            }
+        });
+
+        while let Some(code) = codes.next() {
+            match code {
+                0 => self.style = SpanStyle::default(),
+                1 => self.style.bold = true,
+                3 => self.style.italic = true,
+                4 => self.style.underline = true,
+                21 | 22 => self.style.bold = false,
+                23 => self.style.italic = false,
+                24 => self.style.underline = false,
+                30..=37 => self.style.color = Some(palette_color((code - 30) as u8)),
+                38 => self.style.color = Self::extended_color(&mut codes),
+                39 => self.style.color = None,
+                40..=47 => self.style.background = Some(palette_color((code - 40) as u8)),
+                48 => self.style.background = Self::extended_color(&mut codes),
+                49 => self.style.background = None,
+                90..=97 => self.style.color = Some(palette_color((code - 90 + 8) as u8)),
+                100..=107 => self.style.background = Some(palette_color((code - 100 + 8) as u8)),
+                _ => {}
+            }
+        }
+    }
+
+    // 38 and 48 are followed by either 5;n (256 colour) or 2;r;g;b (truecolor)
+    fn extended_color(codes: &mut impl Iterator<Item = u16>) -> Option<Color32> {
+        let byte = |v: Option<u16>| v.and_then(|v| u8::try_from(v).ok());
+        match codes.next() {
+            Some(5) => byte(codes.next()).map(palette_color),
+            Some(2) => {
+                let (r, g, b) = (byte(codes.next()), byte(codes.next()), byte(codes.next()));
+                Some(Color32::from_rgb(r?, g?, b?))
+            }
+            _ => None,
+        }
+    }
+}
+
+// text between escape sequences goes out in one chunk
+fn push_run(
+    ranges: &mut Vec<(Range<usize>, SpanStyle)>,
+    out: &mut String,
+    text: &str,
+    style: SpanStyle,
+) {
+    if text.is_empty() {
+        return;
+    }
+    let start = out.len();
+    out.push_str(text);
+    if style.is_plain() {
+        return;
+    }
+    match ranges.last_mut() {
+        Some((range, last)) if range.end == start && *last == style => {
+            range.end = out.len();
+        }
+        _ => ranges.push((start..out.len(), style)),
+    }
+}
+
+#[test]
+fn test_ansi_strip() {
+    let mut parser = AnsiParser::default();
+    let mut out = String::new();
+    let ranges = parser.parse("plain \x1b[31mred\x1b[0m plain\x1b[2K", &mut out);
+    assert_eq!(out, "plain red plain");
+    assert_eq!(
+        ranges,
+        vec![(
+            6..9,
+            SpanStyle {
+                color: Some(Color32::from_rgb(205, 0, 0)),
+                ..Default::default()
+            }
+        )]
+    );
+}
+#[test]
+fn test_ansi_extended() {
+    let mut parser = AnsiParser::default();
+    let mut out = String::from("xx");
+    let ranges = parser.parse(
+        "\x1b[1;38;5;196;48;2;1;2;3mA\x1b[22;4mB\x1b[39;49m\x1b]0;title\x07C",
+        &mut out,
+    );
+    assert_eq!(out, "xxABC");
+    assert_eq!(ranges.len(), 3);
+    assert_eq!(ranges[0].0, 2..3);
+    assert!(ranges[0].1.bold);
+    assert_eq!(ranges[0].1.color, Some(Color32::from_rgb(255, 0, 0)));
+    assert_eq!(ranges[0].1.background, Some(Color32::from_rgb(1, 2, 3)));
+    assert_eq!(ranges[1].0, 3..4);
+    assert!(!ranges[1].1.bold && ranges[1].1.underline);
+    // style carries on into the next write
+    assert_eq!(ranges[2].1.color, None);
+    assert!(ranges[2].1.underline);
+    let ranges = parser.parse("D\x1b[mE", &mut out);
+    assert_eq!(out, "xxABCDE");
+    assert_eq!(
+        ranges,
+        vec![(
+            5..6,
+            SpanStyle {
+                underline: true,
+                ..Default::default()
+            }
+        )]
+    );
+}
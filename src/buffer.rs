@@ -0,0 +1,74 @@
+use std::{collections::VecDeque, ops::Range};
+
+use egui::{text::LayoutJob, Ui};
+
+use crate::style::{self, SpanStyle};
+
+/// One line of console output
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct OutputLine {
+    pub(crate) text: String,
+    // styled byte ranges of `text`, sorted and non overlapping
+    pub(crate) styles: Vec<(Range<usize>, SpanStyle)>,
+}
+
+impl OutputLine {
+    pub(crate) fn plain(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            styles: Vec::new(),
+        }
+    }
+    pub(crate) fn push_str(&mut self, text: &str, style: SpanStyle) {
+        let start = self.text.len();
+        self.text.push_str(text);
+        if !style.is_plain() && start != self.text.len() {
+            self.styles.push((start..self.text.len(), style));
+        }
+    }
+    pub(crate) fn layout_job(&self, ui: &Ui) -> LayoutJob {
+        style::layout_job(ui, &self.text, &self.styles, f32::INFINITY)
+    }
+}
+
+/// The console output. A ring of lines, once it is full the oldest line
+/// is dropped for every new one.
+#[derive(Debug, Default)]
+pub(crate) struct ScrollBack {
+    lines: VecDeque<OutputLine>,
+}
+
+impl ScrollBack {
+    pub(crate) fn push(&mut self, line: OutputLine, limit: usize) {
+        // a loop rather than an if in case the limit was lowered
+        while self.lines.len() >= limit.max(1) {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line);
+    }
+    pub(crate) fn len(&self) -> usize {
+        self.lines.len()
+    }
+    pub(crate) fn clear(&mut self) {
+        self.lines.clear();
+    }
+    pub(crate) fn range(&self, rows: Range<usize>) -> impl Iterator<Item = &OutputLine> {
+        self.lines.range(rows)
+    }
+}
+
+#[test]
+fn test_scrollback_ring() {
+    let mut sb = ScrollBack::default();
+    for i in 0..10 {
+        sb.push(OutputLine::plain(i.to_string()), 4);
+    }
+    assert_eq!(sb.len(), 4);
+    assert_eq!(
+        sb.range(0..4).map(|l| l.text.as_str()).collect::<Vec<_>>(),
+        vec!["6", "7", "8", "9"]
+    );
+    assert_eq!(sb.range(1..3).next().unwrap().text, "7");
+    sb.push(OutputLine::plain("x"), 2);
+    assert_eq!(sb.len(), 2);
+}
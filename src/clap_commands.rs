@@ -0,0 +1,413 @@
+use clap::{error::ErrorKind, Arg, ArgMatches, ValueHint};
+
+use crate::{
+    command::{split_args, CommandContext, CommandResult},
+    completion::{Candidate, Completer, CompletionContext, FsCompleter, FsFilter},
+    ConsoleWindow,
+};
+
+type ClapHandler<T> = Box<dyn FnMut(&ArgMatches, &mut CommandContext<'_, T>) -> CommandResult>;
+
+/// Commands defined with a [`clap::Command`]
+///
+/// Available with the `clap` feature. Lines are split into arguments the same way tab completion
+/// splits them, parsed by clap and handed to the handler for the subcommand. Parse errors, help and version
+/// output are written with clap's styling. Tab completion comes from the command definition: subcommands,
+/// long and short flags, possible values, and file or directory paths where the value hint asks for them
+/// (or gives no hint).
+///
+/// With a [`clap::Command::multicall`] command the first word picks the subcommand, which is how
+/// a console normally works. Otherwise the whole line is the arguments to the command.
+///
+/// ```ignore
+/// let mut commands = ClapCommands::new(syntax())
+///     .handler("cd", |args, ctx| {
+///         std::env::set_current_dir(args.get_one::<String>("directory").unwrap())?;
+///         Ok(())
+///     })
+///     .handler("dark", |_, ctx: &mut CommandContext<egui::Context>| {
+///         ctx.state.set_visuals(egui::Visuals::dark());
+///         Ok(())
+///     });
+/// commands.install(&mut console);
+/// ...
+/// if let ConsoleEvent::Command(line) = console.draw(ui) {
+///     commands.dispatch(&line, &mut console, &mut ctx.clone());
+///     console.prompt();
+/// }
+/// ```
+pub struct ClapCommands<T> {
+    command: clap::Command,
+    handlers: Vec<(String, ClapHandler<T>)>,
+}
+
+impl<T> ClapCommands<T> {
+    /// Create from a clap command definition
+    pub fn new(mut command: clap::Command) -> Self {
+        // fills in the generated help and version flags, so they complete too
+        command.build();
+        Self {
+            command,
+            handlers: Vec::new(),
+        }
+    }
+    /// Set the handler for a subcommand
+    /// # Arguments
+    /// * `name` - the subcommand name (not an alias). Use the command's own name for a command without subcommands
+    /// * `handler` - called with the subcommand's matches and a [`CommandContext`]
+    ///
+    pub fn handler(
+        mut self,
+        name: &str,
+        handler: impl FnMut(&ArgMatches, &mut CommandContext<'_, T>) -> CommandResult + 'static,
+    ) -> Self {
+        self.handlers.retain(|(n, _)| n != name);
+        self.handlers.push((name.to_string(), Box::new(handler)));
+        self
+    }
+    /// Use the command definition for the console's tab completion, see [`ClapCompleter`]
+    pub fn install(&self, console: &mut ConsoleWindow) {
+        console.set_completer(self.completer());
+    }
+    /// A completer for the command definition
+    pub fn completer(&self) -> ClapCompleter {
+        ClapCompleter {
+            command: self.command.clone(),
+        }
+    }
+    /// Run a command line
+    /// # Arguments
+    /// * `line` - the line from [`crate::ConsoleEvent::Command`]
+    /// * `console` - where output goes
+    /// * `state` - passed to the handler
+    ///
+    /// # Returns
+    /// * `bool` - false if clap could not parse the line (the console has already been told why)
+    ///
+    pub fn dispatch(&mut self, line: &str, console: &mut ConsoleWindow, state: &mut T) -> bool {
+        let mut args = split_args(line);
+        if args.is_empty() {
+            return true;
+        }
+        if !self.command.is_multicall_set() {
+            args.insert(0, self.command.get_name().to_string());
+        }
+        let matches = match self.command.try_get_matches_from_mut(args) {
+            Ok(matches) => matches,
+            Err(err) => {
+                console.write(&err.render().ansi().to_string());
+                // help and version come back as errors but they are not
+                let ok = matches!(
+                    err.kind(),
+                    ErrorKind::DisplayHelp
+                        | ErrorKind::DisplayVersion
+                        | ErrorKind::DisplayHelpOnMissingArgumentOrSubcommand
+                );
+                console.command_status(ok);
+                return ok;
+            }
+        };
+        let (name, matches) = matches
+            .subcommand()
+            .unwrap_or((self.command.get_name(), &matches));
+        match self.handlers.iter_mut().find(|(n, _)| n == name) {
+            Some((_, handler)) => {
+                let mut ctx = CommandContext { console, state };
+                let result = handler(matches, &mut ctx);
+                if let Err(err) = &result {
+                    ctx.console.write_error(&err.to_string());
+                }
+                ctx.console.command_status(result.is_ok());
+            }
+            None => {
+                console.write_error(&format!("'{}' is not implemented", name));
+                console.command_status(false);
+            }
+        }
+        true
+    }
+}
+
+/// Completes from a [`clap::Command`] definition
+///
+/// Get one from [`ClapCommands::completer`]. It offers subcommands, long and short flags, possible values,
+/// and paths for arguments whose value hint is a path (or that have no hint).
+#[derive(Debug, Clone)]
+pub struct ClapCompleter {
+    command: clap::Command,
+}
+
+impl Completer for ClapCompleter {
+    fn complete(&self, ctx: &CompletionContext<'_>) -> Vec<Candidate> {
+        match clap_complete(&self.command, &ctx.args) {
+            ClapCompletion::Words(words) => words
+                .into_iter()
+                .filter(|(word, _)| ctx.matches(word))
+                .map(|(word, description)| {
+                    Candidate::new(ctx.quote_if_needed(&word), ctx.word_range())
+                        .display(word)
+                        .description(description)
+                })
+                .collect(),
+            ClapCompletion::Path { prefix, dirs_only } => {
+                if !ctx.prefix().starts_with(prefix.as_str()) {
+                    return Vec::new();
+                }
+                let filter = if dirs_only {
+                    FsFilter::DirsOnly
+                } else {
+                    FsFilter::All
+                };
+                FsCompleter::new()
+                    .filter(filter)
+                    .complete_after(ctx, prefix.len())
+            }
+        }
+    }
+}
+
+// what can go at the cursor
+#[derive(Debug, PartialEq)]
+pub(crate) enum ClapCompletion {
+    // whole replacement words and their descriptions
+    Words(Vec<(String, String)>),
+    // a path, `prefix` (eg "--file=") is kept in front of it
+    Path { prefix: String, dirs_only: bool },
+}
+
+// work out the completions for the last of `args`, the ones before it are complete
+pub(crate) fn clap_complete(command: &clap::Command, args: &[&str]) -> ClapCompletion {
+    let Some((partial, done)) = args.split_last() else {
+        return ClapCompletion::Words(Vec::new());
+    };
+    let mut cmd = command;
+    let mut positional = 0;
+    // the option waiting for its value
+    let mut pending: Option<&Arg> = None;
+    for token in done.iter().map(|t| unquote(t)) {
+        if pending.take().is_some() {
+            continue;
+        }
+        if let Some(long) = token.strip_prefix("--") {
+            if !long.contains('=') {
+                pending = find_long(cmd, long).filter(|a| takes_value(a));
+            }
+        } else if let Some(shorts) = token.strip_prefix('-').filter(|s| !s.is_empty()) {
+            // -abc, only the last flag can have a separate value
+            pending = shorts
+                .chars()
+                .last()
+                .and_then(|c| cmd.get_arguments().find(|a| a.get_short() == Some(c)))
+                .filter(|a| takes_value(a) && shorts.len() == 1);
+        } else if let Some(sub) = find_subcommand(cmd, token) {
+            cmd = sub;
+            positional = 0;
+        } else {
+            positional += 1;
+        }
+    }
+    let partial = unquote(partial);
+    if let Some(arg) = pending {
+        return complete_value(arg, "");
+    }
+    if let Some(long) = partial.strip_prefix("--") {
+        if let Some((name, _)) = long.split_once('=') {
+            return match find_long(cmd, name) {
+                Some(arg) => complete_value(arg, &format!("--{}=", name)),
+                None => ClapCompletion::Words(Vec::new()),
+            };
+        }
+    }
+    if partial.starts_with('-') {
+        let mut words: Vec<(String, String)> = visible_args(cmd)
+            .filter(|a| !a.is_positional())
+            .flat_map(|a| {
+                let help = a.get_help().map(|h| h.to_string()).unwrap_or_default();
+                a.get_long()
+                    .map(|l| format!("--{}", l))
+                    .into_iter()
+                    .chain(a.get_short().map(|s| format!("-{}", s)))
+                    .map(move |flag| (flag, help.clone()))
+            })
+            .collect();
+        // shorts are only offered for a bare "-"
+        if partial.starts_with("--") {
+            words.retain(|(w, _)| w.starts_with("--"));
+        }
+        return ClapCompletion::Words(words);
+    }
+    let subcommands: Vec<(String, String)> = cmd
+        .get_subcommands()
+        .filter(|s| !s.is_hide_set())
+        .flat_map(|s| {
+            let about = s.get_about().map(|a| a.to_string()).unwrap_or_default();
+            std::iter::once(s.get_name())
+                .chain(s.get_visible_aliases())
+                .map(move |name| (name.to_string(), about.clone()))
+        })
+        .collect();
+    match visible_args(cmd)
+        .filter(|a| a.is_positional())
+        .nth(positional)
+    {
+        Some(arg) => match complete_value(arg, "") {
+            ClapCompletion::Words(mut words) => {
+                words.extend(subcommands);
+                ClapCompletion::Words(words)
+            }
+            path => path,
+        },
+        None => ClapCompletion::Words(subcommands),
+    }
+}
+
+fn complete_value(arg: &Arg, prefix: &str) -> ClapCompletion {
+    let values = arg.get_possible_values();
+    if !values.is_empty() {
+        return ClapCompletion::Words(
+            values
+                .iter()
+                .filter(|v| !v.is_hide_set())
+                .map(|v| {
+                    let help = v.get_help().map(|h| h.to_string()).unwrap_or_default();
+                    (format!("{}{}", prefix, v.get_name()), help)
+                })
+                .collect(),
+        );
+    }
+    match arg.get_value_hint() {
+        ValueHint::Unknown
+        | ValueHint::AnyPath
+        | ValueHint::FilePath
+        | ValueHint::ExecutablePath => ClapCompletion::Path {
+            prefix: prefix.to_string(),
+            dirs_only: false,
+        },
+        ValueHint::DirPath => ClapCompletion::Path {
+            prefix: prefix.to_string(),
+            dirs_only: true,
+        },
+        _ => ClapCompletion::Words(Vec::new()),
+    }
+}
+
+fn visible_args(cmd: &clap::Command) -> impl Iterator<Item = &Arg> {
+    cmd.get_arguments().filter(|a| !a.is_hide_set())
+}
+
+fn takes_value(arg: &Arg) -> bool {
+    arg.get_action().takes_values()
+}
+
+fn find_long<'a>(cmd: &'a clap::Command, long: &str) -> Option<&'a Arg> {
+    cmd.get_arguments().find(|a| {
+        a.get_long() == Some(long)
+            || a.get_all_aliases()
+                .is_some_and(|aliases| aliases.contains(&long))
+    })
+}
+
+fn find_subcommand<'a>(cmd: &'a clap::Command, name: &str) -> Option<&'a clap::Command> {
+    cmd.get_subcommands()
+        .find(|s| s.get_name() == name || s.get_all_aliases().any(|a| a == name))
+}
+
+fn unquote(arg: &str) -> &str {
+    arg.trim_start_matches(['"', '\''])
+        .trim_end_matches(['"', '\''])
+}
+
+#[test]
+fn test_clap_complete() {
+    use clap::{arg, Command};
+    let mut cmd = Command::new("app")
+        .multicall(true)
+        .subcommand(Command::new("cd").arg(arg!(<dir>).value_hint(ValueHint::DirPath)))
+        .subcommand(
+            Command::new("mode")
+                .visible_alias("m")
+                .arg(arg!(--level <LEVEL>).value_parser(["low", "high"]))
+                .arg(
+                    Arg::new("verbose")
+                        .short('v')
+                        .long("verbose")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(arg!([style]).value_parser(["dark", "light"])),
+        );
+    cmd.build();
+    let words = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    let complete = |args: &[&str]| match clap_complete(&cmd, args) {
+        ClapCompletion::Words(words) => words.into_iter().map(|(w, _)| w).collect::<Vec<_>>(),
+        path => panic!("{:?}", path),
+    };
+    assert_eq!(complete(&["c"]), words(&["cd", "mode", "m", "help"]));
+    assert_eq!(
+        clap_complete(&cmd, &["cd", "sr"]),
+        ClapCompletion::Path {
+            prefix: String::new(),
+            dirs_only: true
+        }
+    );
+    assert_eq!(
+        complete(&["mode", "--"]),
+        words(&["--level", "--verbose", "--help"])
+    );
+    assert_eq!(
+        complete(&["m", "-"]),
+        words(&["--level", "--verbose", "-v", "--help", "-h"])
+    );
+    assert_eq!(complete(&["mode", "--level", ""]), words(&["low", "high"]));
+    assert_eq!(
+        complete(&["mode", "--level=h"]),
+        words(&["--level=low", "--level=high"])
+    );
+    assert_eq!(complete(&["mode", "-v", "d"]), words(&["dark", "light"]));
+}
+
+#[test]
+fn test_clap_dispatch() {
+    use clap::{arg, Command};
+    let syntax = Command::new("app")
+        .multicall(true)
+        .subcommand(Command::new("cd").arg(arg!(<dir>)))
+        .subcommand(
+            Command::new("greet")
+                .arg(arg!(--name <NAME>))
+                .arg(arg!([who])),
+        )
+        .subcommand(
+            Command::new("mode")
+                .visible_alias("m")
+                .arg(arg!([style]).value_parser(["dark", "light"])),
+        );
+    let mut console = crate::ConsoleBuilder::new().build();
+    let mut commands = ClapCommands::new(syntax)
+        .handler("mode", |args, ctx: &mut CommandContext<'_, Vec<String>>| {
+            ctx.state
+                .push(args.get_one::<String>("style").cloned().unwrap_or_default());
+            Ok(())
+        })
+        .handler(
+            "greet",
+            |args, ctx: &mut CommandContext<'_, Vec<String>>| {
+                for id in ["name", "who"] {
+                    ctx.state
+                        .push(args.get_one::<String>(id).cloned().unwrap_or_default());
+                }
+                Ok(())
+            },
+        );
+    let mut seen = Vec::new();
+    assert!(commands.dispatch("m 'dark'", &mut console, &mut seen));
+    assert_eq!(seen, vec!["dark"]);
+    assert!(!commands.dispatch("mode blue", &mut console, &mut seen));
+    assert!(console.scrollback.len() > 0);
+    console.clear();
+    assert!(commands.dispatch("cd /", &mut console, &mut seen));
+    let line = &console.scrollback.range(0..1).next().unwrap().text;
+    assert_eq!(line, "'cd' is not implemented");
+    seen.clear();
+    assert!(commands.dispatch("greet --name=\"a b\" x\"y z\"", &mut console, &mut seen));
+    assert_eq!(seen, vec!["a b", "xy z"]);
+}
@@ -0,0 +1,374 @@
+use std::error::Error;
+
+use crate::ConsoleWindow;
+
+/// What a command handler returns, errors are written to the console in the theme's error style
+pub type CommandResult = Result<(), Box<dyn Error>>;
+
+type Handler<T> = Box<dyn FnMut(&[String], &mut CommandContext<'_, T>) -> CommandResult>;
+
+/// What a command handler gets to work with
+pub struct CommandContext<'a, T> {
+    /// The console, for writing output
+    pub console: &'a mut ConsoleWindow,
+    /// The application state passed to [`CommandRegistry::dispatch`]
+    pub state: &'a mut T,
+}
+
+/// A command for a [`CommandRegistry`]
+///
+/// ```ignore
+/// Command::new("cd", |args, ctx| {
+///     std::env::set_current_dir(args.first().ok_or("missing directory")?)?;
+///     ctx.console.write(&std::env::current_dir()?.display().to_string());
+///     Ok(())
+/// })
+/// .alias("chdir")
+/// .description("change the current directory")
+/// .usage("cd <directory>")
+/// ```
+pub struct Command<T> {
+    name: String,
+    aliases: Vec<String>,
+    description: String,
+    usage: String,
+    handler: Handler<T>,
+}
+
+impl<T> Command<T> {
+    /// Create a command
+    /// # Arguments
+    /// * `name` - what the user types
+    /// * `handler` - called with the arguments (the command name is not included) and a [`CommandContext`]
+    ///
+    pub fn new(
+        name: &str,
+        handler: impl FnMut(&[String], &mut CommandContext<'_, T>) -> CommandResult + 'static,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            aliases: Vec::new(),
+            description: String::new(),
+            usage: String::new(),
+            handler: Box::new(handler),
+        }
+    }
+    /// Another name for the command
+    pub fn alias(mut self, alias: &str) -> Self {
+        self.aliases.push(alias.to_string());
+        self
+    }
+    /// One line description, shown by `help`
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = description.to_string();
+        self
+    }
+    /// How to use the command, shown by `help <command>`
+    pub fn usage(mut self, usage: &str) -> Self {
+        self.usage = usage.to_string();
+        self
+    }
+    fn names(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.name.as_str()).chain(self.aliases.iter().map(String::as_str))
+    }
+}
+
+// the commands every registry has
+const BUILTINS: [(&str, &str); 3] = [
+    ("help", "list the commands, or show how to use one"),
+    ("clear", "clear the console"),
+    ("history", "show the command history"),
+];
+
+/// A set of commands and their handlers
+///
+/// Saves every app writing its own dispatch. `T` is the application state handed to the handlers.
+/// `help`, `clear` and `history` are built in, unless you register your own commands with those names.
+///
+/// ```ignore
+/// let mut registry = CommandRegistry::new();
+/// registry.register(Command::new("dark", |_, ctx: &mut CommandContext<egui::Context>| {
+///     ctx.state.set_visuals(egui::Visuals::dark());
+///     Ok(())
+/// }));
+/// registry.install(&mut console);
+/// ...
+/// if let ConsoleEvent::Command(line) = console.draw(ui) {
+///     registry.dispatch(&line, &mut console, &mut ctx.clone());
+///     console.prompt();
+/// }
+/// ```
+pub struct CommandRegistry<T> {
+    commands: Vec<Command<T>>,
+}
+
+impl<T> Default for CommandRegistry<T> {
+    fn default() -> Self {
+        Self {
+            commands: Vec::new(),
+        }
+    }
+}
+
+impl<T> CommandRegistry<T> {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Add a command, replacing any command with the same name
+    pub fn register(&mut self, command: Command<T>) -> &mut Self {
+        self.commands.retain(|c| c.name != command.name);
+        self.commands.push(command);
+        self
+    }
+    /// Add the command names (and aliases) to the console's tab completion table
+    pub fn install(&self, console: &mut ConsoleWindow) {
+        let table = console.command_table_mut();
+        let names = BUILTINS
+            .iter()
+            .map(|(name, _)| *name)
+            .chain(self.commands.iter().flat_map(Command::names));
+        for name in names {
+            if !table.iter().any(|c| c == name) {
+                table.push(name.to_string());
+            }
+        }
+    }
+    /// Run a command line
+    /// # Arguments
+    /// * `line` - the line from [`crate::ConsoleEvent::Command`]
+    /// * `console` - where output goes
+    /// * `state` - passed to the handler
+    ///
+    /// # Returns
+    /// * `bool` - false if the command was not found (the console has already been told)
+    ///
+    pub fn dispatch(&mut self, line: &str, console: &mut ConsoleWindow, state: &mut T) -> bool {
+        let args = split_args(line);
+        let Some((name, args)) = args.split_first() else {
+            return true;
+        };
+        if let Some(command) = self.find_mut(name) {
+            let mut ctx = CommandContext { console, state };
+            let result = (command.handler)(args, &mut ctx);
+            if let Err(err) = &result {
+                ctx.console.write_error(&err.to_string());
+            }
+            ctx.console.command_status(result.is_ok());
+            return true;
+        }
+        let success = match name.as_str() {
+            "help" => self.help(args, console),
+            "clear" => {
+                console.clear();
+                true
+            }
+            "history" => {
+                let history = console.get_history();
+                for (i, line) in history.iter().enumerate() {
+                    console.write(&format!("{:>4}  {}", i, line));
+                }
+                true
+            }
+            _ => {
+                let mut msg = format!("unknown command '{}'", name);
+                if let Some(suggestion) = self.suggest(name) {
+                    msg.push_str(&format!(", did you mean '{}'?", suggestion));
+                }
+                console.write_error(&msg);
+                console.command_status(false);
+                return false;
+            }
+        };
+        console.command_status(success);
+        true
+    }
+    fn find_mut(&mut self, name: &str) -> Option<&mut Command<T>> {
+        self.commands
+            .iter_mut()
+            .find(|c| c.names().any(|n| n == name))
+    }
+    // false if asked about a command that does not exist
+    fn help(&self, args: &[String], console: &mut ConsoleWindow) -> bool {
+        match args.first() {
+            None => {
+                let width = self
+                    .commands
+                    .iter()
+                    .map(|c| c.name.len())
+                    .chain(BUILTINS.iter().map(|(name, _)| name.len()))
+                    .max()
+                    .unwrap_or(0);
+                let builtins = BUILTINS
+                    .iter()
+                    .filter(|(name, _)| self.commands.iter().all(|c| c.name != *name))
+                    .map(|(name, description)| (*name, *description, Vec::new()));
+                let commands = self.commands.iter().map(|c| {
+                    (
+                        c.name.as_str(),
+                        c.description.as_str(),
+                        c.aliases.iter().map(String::as_str).collect(),
+                    )
+                });
+                for (name, description, aliases) in commands.chain(builtins) {
+                    let mut line = format!("{:<width$}  {}", name, description);
+                    if !aliases.is_empty() {
+                        line.push_str(&format!(" (aliases: {})", aliases.join(", ")));
+                    }
+                    console.write(line.trim_end());
+                }
+                true
+            }
+            Some(name) => {
+                if let Some(command) = self.commands.iter().find(|c| c.names().any(|n| n == name)) {
+                    if !command.description.is_empty() {
+                        console.write(&command.description);
+                    }
+                    let usage = if command.usage.is_empty() {
+                        &command.name
+                    } else {
+                        &command.usage
+                    };
+                    console.write(&format!("usage: {}", usage));
+                    if !command.aliases.is_empty() {
+                        console.write(&format!("aliases: {}", command.aliases.join(", ")));
+                    }
+                    true
+                } else if let Some((_, description)) = BUILTINS.iter().find(|(n, _)| n == name) {
+                    console.write(description);
+                    true
+                } else {
+                    console.write_error(&format!("no help for unknown command '{}'", name));
+                    false
+                }
+            }
+        }
+    }
+    // the closest known name, if it is close enough to be a typo
+    fn suggest(&self, name: &str) -> Option<&str> {
+        BUILTINS
+            .iter()
+            .map(|(name, _)| *name)
+            .chain(self.commands.iter().flat_map(Command::names))
+            .map(|candidate| (edit_distance(name, candidate), candidate))
+            .filter(|(distance, _)| *distance <= (name.chars().count() / 3).max(1))
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, candidate)| candidate)
+    }
+}
+
+// split a command line into arguments like a shell does, whitespace separates
+// arguments and quotes group words anywhere in an argument and are removed
+pub(crate) fn split_args(line: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut arg: Option<String> = None;
+    let mut quote = None;
+    for ch in line.chars() {
+        match quote {
+            Some(q) if ch == q => quote = None,
+            Some(_) => arg.get_or_insert_with(String::new).push(ch),
+            None if ch.is_whitespace() => args.extend(arg.take()),
+            None => {
+                let arg = arg.get_or_insert_with(String::new);
+                match ch {
+                    '"' | '\'' => quote = Some(ch),
+                    _ => arg.push(ch),
+                }
+            }
+        }
+    }
+    args.extend(arg);
+    args
+}
+
+// levenshtein distance
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitute.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+#[test]
+fn test_registry() {
+    let mut console = crate::ConsoleBuilder::new().build();
+    let mut registry = CommandRegistry::new();
+    registry.register(
+        Command::new("add", |args, ctx: &mut CommandContext<'_, i32>| {
+            for arg in args {
+                *ctx.state += arg.parse::<i32>()?;
+            }
+            Ok(())
+        })
+        .alias("plus")
+        .description("add numbers"),
+    );
+    registry.install(&mut console);
+    assert_eq!(console.command_table_mut().len(), 5);
+
+    let mut total = 0;
+    assert!(registry.dispatch("add 1 2", &mut console, &mut total));
+    assert!(registry.dispatch("plus '3'", &mut console, &mut total));
+    assert_eq!(total, 6);
+    assert!(registry.dispatch("add x", &mut console, &mut total));
+    assert!(!registry.dispatch("ad 1", &mut console, &mut total));
+    let last = console.scrollback.len() - 1;
+    let line = &console
+        .scrollback
+        .range(last..last + 1)
+        .next()
+        .unwrap()
+        .text;
+    assert_eq!(line, "unknown command 'ad', did you mean 'add'?");
+    assert!(!registry.dispatch("zzzzzz", &mut console, &mut total));
+    registry.dispatch("clear", &mut console, &mut total);
+    assert_eq!(console.scrollback.len(), 0);
+    registry.dispatch("help", &mut console, &mut total);
+    assert_eq!(console.scrollback.len(), 4);
+
+    let status = |console: &ConsoleWindow| console.history_entries().back().unwrap().success;
+    console.history_start("help add");
+    registry.dispatch("help add", &mut console, &mut total);
+    assert_eq!(status(&console), Some(true));
+    console.history_start("help nothere");
+    registry.dispatch("help nothere", &mut console, &mut total);
+    assert_eq!(status(&console), Some(false));
+
+    // quotes inside a word are removed and keep what came before them
+    registry.register(Command::new(
+        "echo",
+        |args, ctx: &mut CommandContext<'_, i32>| {
+            ctx.console.write(&args.join("|"));
+            Ok(())
+        },
+    ));
+    console.clear();
+    registry.dispatch("echo --name=\"a b\" x\"y z\"", &mut console, &mut total);
+    let line = &console.scrollback.range(0..1).next().unwrap().text;
+    assert_eq!(line, "--name=a b|xy z");
+}
+
+#[test]
+fn test_split_args() {
+    assert_eq!(split_args(" cd  foo "), vec!["cd", "foo"]);
+    assert_eq!(split_args("cd \"foo bar\""), vec!["cd", "foo bar"]);
+    assert_eq!(split_args("cd 'foo bar"), vec!["cd", "foo bar"]);
+    assert_eq!(split_args("set --name=\"a b\""), vec!["set", "--name=a b"]);
+    assert_eq!(split_args("x\"y z\"w 'it\"s'"), vec!["xy zw", "it\"s"]);
+    assert_eq!(split_args("echo \"\" ''"), vec!["echo", "", ""]);
+}
+
+#[test]
+fn test_edit_distance() {
+    assert_eq!(edit_distance("", "abc"), 3);
+    assert_eq!(edit_distance("kitten", "sitting"), 3);
+    assert_eq!(edit_distance("histroy", "history"), 2);
+}
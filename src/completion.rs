@@ -0,0 +1,843 @@
+use std::{
+    fmt,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use itertools::Itertools;
+
+use crate::{
+    completion_fs::{CompletionFs, StdFs},
+    slot::Slot,
+    ConsoleWindow,
+};
+
+/// One possible completion
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    /// The text that replaces `range`
+    pub replacement: String,
+    /// The byte range of the input line that is replaced
+    pub range: Range<usize>,
+    /// What to show the user, usually the replacement without quotes or leading directories
+    pub display: String,
+    /// An optional one line description
+    pub description: String,
+    /// What sort of thing it is, picks the icon in the completion popup
+    pub kind: CandidateKind,
+}
+
+/// What a [`Candidate`] is
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CandidateKind {
+    /// A command name
+    Command,
+    /// A directory
+    Directory,
+    /// A file
+    File,
+    /// Anything else, shown without an icon
+    #[default]
+    Other,
+}
+
+impl Candidate {
+    /// Create a candidate, the display text is the replacement
+    pub fn new(replacement: impl Into<String>, range: Range<usize>) -> Self {
+        let replacement = replacement.into();
+        Self {
+            display: replacement.clone(),
+            replacement,
+            range,
+            description: String::new(),
+            kind: CandidateKind::Other,
+        }
+    }
+    /// Set the text shown to the user
+    pub fn display(mut self, display: impl Into<String>) -> Self {
+        self.display = display.into();
+        self
+    }
+    /// Set the description
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = description.into();
+        self
+    }
+    /// Set the kind
+    pub fn kind(mut self, kind: CandidateKind) -> Self {
+        self.kind = kind;
+        self
+    }
+}
+
+/// How what has been typed is matched against the candidates
+///
+/// Set with [`crate::ConsoleBuilder::match_mode`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MatchMode {
+    /// The candidate starts with what was typed
+    #[default]
+    Prefix,
+    /// The candidate starts with what was typed, ignoring case
+    CaseInsensitive,
+    /// What was typed appears anywhere in the candidate, ignoring case. Earlier is better
+    Substring,
+    /// The typed characters appear in order in the candidate, ignoring case. Candidates are ranked,
+    /// runs of characters and characters at the start of words ("." "_" "-" "/" or a capital) score higher
+    Fuzzy,
+}
+
+/// What tab does when there is more than one candidate
+///
+/// Set with [`crate::ConsoleBuilder::completion_style`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CompletionStyle {
+    /// Open the popup straight away
+    #[default]
+    Popup,
+    /// Like bash, the first tab inserts what the candidates have in common and the second opens the popup
+    Bash,
+}
+
+/// A successful match, see [`MatchMode::find`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    /// Higher is better
+    pub score: i32,
+    /// The byte offsets of the matched characters in the candidate
+    pub positions: Vec<usize>,
+}
+
+impl MatchMode {
+    /// Match `typed` against `candidate`
+    /// # Returns
+    /// * `Option<Match>` - None if it does not match
+    ///
+    pub fn find(&self, typed: &str, candidate: &str) -> Option<Match> {
+        let positions = |start: usize, len: usize| {
+            candidate[start..start + len]
+                .char_indices()
+                .map(|(i, _)| start + i)
+                .collect()
+        };
+        match self {
+            MatchMode::Prefix => candidate.starts_with(typed).then(|| Match {
+                score: 0,
+                positions: positions(0, typed.len()),
+            }),
+            MatchMode::CaseInsensitive => {
+                let len = lowercase_prefix_len(typed, candidate)?;
+                Some(Match {
+                    score: 0,
+                    positions: positions(0, len),
+                })
+            }
+            MatchMode::Substring => candidate
+                .char_indices()
+                .map(|(i, _)| i)
+                .find_map(|start| {
+                    let len = lowercase_prefix_len(typed, &candidate[start..])?;
+                    Some(Match {
+                        score: -(start as i32),
+                        positions: positions(start, len),
+                    })
+                })
+                .or_else(|| {
+                    typed.is_empty().then(|| Match {
+                        score: 0,
+                        positions: Vec::new(),
+                    })
+                }),
+            MatchMode::Fuzzy => fuzzy(typed, candidate),
+        }
+    }
+    // like `find`, but when `typed` and `candidate` share everything up to the last separator
+    // ("/" "\\" or "=") of `typed` only what comes after it is matched, the way the completers
+    // match a file in a directory or the value of an option
+    pub(crate) fn find_word(&self, typed: &str, candidate: &str) -> Option<Match> {
+        let head = typed
+            .char_indices()
+            .filter(|(_, c)| matches!(c, '/' | '\\' | '='))
+            .map(|(i, c)| i + c.len_utf8())
+            .rfind(|&end| candidate.starts_with(&typed[..end]))
+            .unwrap_or(0);
+        let found = self.find(&typed[head..], &candidate[head..])?;
+        let positions = candidate[..head]
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain(found.positions.into_iter().map(|i| head + i))
+            .collect();
+        Some(Match {
+            score: found.score,
+            positions,
+        })
+    }
+}
+
+// the length in `candidate` of `typed`, if `candidate` starts with it ignoring case
+fn lowercase_prefix_len(typed: &str, candidate: &str) -> Option<usize> {
+    let mut chars = candidate.char_indices();
+    for t in typed.chars() {
+        let (_, c) = chars.next()?;
+        if !c.to_lowercase().eq(t.to_lowercase()) {
+            return None;
+        }
+    }
+    Some(chars.next().map_or(candidate.len(), |(i, _)| i))
+}
+
+// greedy subsequence match, taking the next character at a word start when there is one
+fn fuzzy(typed: &str, candidate: &str) -> Option<Match> {
+    let chars: Vec<(usize, char)> = candidate.char_indices().collect();
+    let word_start = |i: usize| {
+        i == 0 || {
+            let (prev, cur) = (chars[i - 1].1, chars[i].1);
+            matches!(prev, '.' | '_' | '-' | '/' | '\\' | ' ' | ':')
+                || (prev.is_lowercase() && cur.is_uppercase())
+        }
+    };
+    let same = |a: char, b: char| a.to_lowercase().eq(b.to_lowercase());
+    let mut score = 0;
+    let mut positions = Vec::new();
+    let mut next = 0;
+    let mut last: Option<usize> = None;
+    for t in typed.chars() {
+        let rest = next..chars.len();
+        // a match straight after the last one keeps a run going, otherwise prefer a word start
+        let found = if last.is_some_and(|l| l + 1 == next)
+            && chars.get(next).is_some_and(|c| same(c.1, t))
+        {
+            next
+        } else {
+            let mut matching = rest.filter(|&i| same(chars[i].1, t));
+            let first = matching.next()?;
+            std::iter::once(first)
+                .chain(matching)
+                .find(|&i| word_start(i))
+                .unwrap_or(first)
+        };
+        score += 16;
+        if last.is_some_and(|l| l + 1 == found) {
+            score += 8;
+        } else if word_start(found) {
+            score += 12;
+        }
+        // gaps cost a little
+        score -= (found - last.map_or(0, |l| l + 1)) as i32;
+        positions.push(chars[found].0);
+        last = Some(found);
+        next = found + 1;
+    }
+    Some(Match { score, positions })
+}
+
+/// What a [`Completer`] is asked to complete
+#[derive(Debug)]
+pub struct CompletionContext<'a> {
+    /// The whole input line
+    pub line: &'a str,
+    /// The cursor position, a byte offset into `line`
+    pub cursor: usize,
+    /// The arguments before the cursor, quotes are left in. The last one is the word
+    /// being completed, it is empty if the cursor follows a space
+    pub args: Vec<&'a str>,
+    commands: &'a [String],
+    quote: char,
+    mode: MatchMode,
+}
+
+impl<'a> CompletionContext<'a> {
+    pub(crate) fn new(
+        line: &'a str,
+        cursor: usize,
+        commands: &'a [String],
+        quote: char,
+        mode: MatchMode,
+    ) -> Self {
+        let mut args = ConsoleWindow::digest_line(&line[..cursor]);
+        if args.is_empty() {
+            args.push("");
+        }
+        Self {
+            line,
+            cursor,
+            args,
+            commands,
+            quote,
+            mode,
+        }
+    }
+    /// The word being completed, as typed
+    pub fn word(&self) -> &'a str {
+        self.args.last().copied().unwrap_or("")
+    }
+    /// The word being completed without its opening quote
+    pub fn prefix(&self) -> &'a str {
+        let word = self.word();
+        word.strip_prefix(['"', '\'']).unwrap_or(word)
+    }
+    /// Which argument is being completed, 0 is the command
+    pub fn arg_index(&self) -> usize {
+        self.args.len() - 1
+    }
+    /// The range a candidate for the word being completed should replace
+    pub fn word_range(&self) -> Range<usize> {
+        self.cursor - self.word().len()..self.cursor
+    }
+    /// The console's command table, see [`ConsoleWindow::command_table_mut`]
+    pub fn command_table(&self) -> &'a [String] {
+        self.commands
+    }
+    /// Does `candidate` match what has been typed so far, using the console's [`MatchMode`]
+    pub fn matches(&self, candidate: &str) -> bool {
+        self.mode.find_word(self.prefix(), candidate).is_some()
+    }
+    /// The console's [`MatchMode`]
+    pub fn match_mode(&self) -> MatchMode {
+        self.mode
+    }
+    /// Quote `text` if it contains a space, using the quote the user typed or the one set by
+    /// [`crate::ConsoleBuilder::tab_quote_character`]
+    pub fn quote_if_needed(&self, text: &str) -> String {
+        if text.contains(' ') {
+            let quote = self
+                .word()
+                .chars()
+                .next()
+                .filter(|c| *c == '"' || *c == '\'');
+            let quote = quote.unwrap_or(self.quote);
+            format!("{}{}{}", quote, text, quote)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// A source of tab completions
+///
+/// Install one with [`crate::ConsoleBuilder::completer`]. Closures taking a [`CompletionContext`]
+/// and returning the candidates are completers, and completers can be combined with [`Completer::chain`].
+///
+/// ```ignore
+/// let ids = |ctx: &CompletionContext| -> Vec<Candidate> {
+///     if ctx.arg_index() != 1 || ctx.args[0] != "select" {
+///         return Vec::new();
+///     }
+///     entity_ids()
+///         .filter(|id| ctx.matches(id))
+///         .map(|id| Candidate::new(id, ctx.word_range()))
+///         .collect()
+/// };
+/// ConsoleBuilder::new().completer(CommandCompleter.chain(ids))
+/// ```
+pub trait Completer {
+    /// The candidates for the word at the cursor
+    fn complete(&self, ctx: &CompletionContext<'_>) -> Vec<Candidate>;
+
+    /// A completer offering the candidates from both
+    fn chain<C: Completer>(self, other: C) -> Chain<Self, C>
+    where
+        Self: Sized,
+    {
+        Chain(self, other)
+    }
+}
+
+impl<F> Completer for F
+where
+    F: Fn(&CompletionContext<'_>) -> Vec<Candidate>,
+{
+    fn complete(&self, ctx: &CompletionContext<'_>) -> Vec<Candidate> {
+        self(ctx)
+    }
+}
+
+impl Completer for Box<dyn Completer> {
+    fn complete(&self, ctx: &CompletionContext<'_>) -> Vec<Candidate> {
+        self.as_ref().complete(ctx)
+    }
+}
+
+impl Completer for Box<dyn Completer + Send> {
+    fn complete(&self, ctx: &CompletionContext<'_>) -> Vec<Candidate> {
+        self.as_ref().complete(ctx)
+    }
+}
+
+/// Two completers together, see [`Completer::chain`]
+#[derive(Debug, Clone)]
+pub struct Chain<A, B>(A, B);
+
+impl<A: Completer, B: Completer> Completer for Chain<A, B> {
+    fn complete(&self, ctx: &CompletionContext<'_>) -> Vec<Candidate> {
+        let mut candidates = self.0.complete(ctx);
+        candidates.extend(self.1.complete(ctx));
+        candidates
+    }
+}
+
+/// Completes the first word from the console's command table
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CommandCompleter;
+
+impl Completer for CommandCompleter {
+    fn complete(&self, ctx: &CompletionContext<'_>) -> Vec<Candidate> {
+        if ctx.arg_index() != 0 {
+            return Vec::new();
+        }
+        ctx.command_table()
+            .iter()
+            .filter(|c| ctx.matches(c))
+            .map(|c| Candidate::new(c.as_str(), ctx.word_range()).kind(CandidateKind::Command))
+            .collect()
+    }
+}
+
+/// Which entries an [`FsCompleter`] offers
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum FsFilter {
+    /// Files and directories
+    #[default]
+    All,
+    /// Only directories, eg for `cd`
+    DirsOnly,
+    /// Directories, and files with one of these extensions (without the dot)
+    Extensions(Vec<String>),
+}
+
+/// Completes file system paths, for every word but the first
+///
+/// ```ignore
+/// let completer = FsCompleter::new()
+///     .root("/home/me/project")
+///     .filter(FsFilter::Extensions(vec!["toml".into(), "json".into()]));
+/// ```
+///
+/// By default `~` and environment variables (`$HOME`, `${HOME}`) are expanded to find the directory
+/// but left as typed in the line, hidden files are only offered when the name typed starts with a dot,
+/// and directories get a trailing separator.
+#[derive(Clone)]
+pub struct FsCompleter {
+    fs: Arc<dyn CompletionFs + Send + Sync>,
+    filter: FsFilter,
+    hidden: bool,
+    expand_home: bool,
+    expand_vars: bool,
+    trailing_separator: bool,
+    root: Option<PathBuf>,
+}
+
+impl Default for FsCompleter {
+    fn default() -> Self {
+        Self {
+            fs: Arc::new(StdFs),
+            filter: FsFilter::All,
+            hidden: false,
+            expand_home: true,
+            expand_vars: true,
+            trailing_separator: true,
+            root: None,
+        }
+    }
+}
+
+impl fmt::Debug for FsCompleter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FsCompleter")
+            .field("filter", &self.filter)
+            .field("hidden", &self.hidden)
+            .field("expand_home", &self.expand_home)
+            .field("expand_vars", &self.expand_vars)
+            .field("trailing_separator", &self.trailing_separator)
+            .field("root", &self.root)
+            .finish_non_exhaustive()
+    }
+}
+
+impl FsCompleter {
+    /// Create a file system completer
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Complete from `fs` rather than the real file system ([`StdFs`])
+    pub fn fs(mut self, fs: impl CompletionFs + Send + Sync + 'static) -> Self {
+        self.fs = Arc::new(fs);
+        self
+    }
+    /// Which entries to offer, the default is [`FsFilter::All`]
+    pub fn filter(mut self, filter: FsFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+    /// Always offer hidden (dot) files, not just when the name typed starts with a dot
+    pub fn hidden(mut self, hidden: bool) -> Self {
+        self.hidden = hidden;
+        self
+    }
+    /// Expand a leading `~` to the home directory
+    pub fn expand_home(mut self, expand: bool) -> Self {
+        self.expand_home = expand;
+        self
+    }
+    /// Expand `$VAR` and `${VAR}`
+    pub fn expand_vars(mut self, expand: bool) -> Self {
+        self.expand_vars = expand;
+        self
+    }
+    /// Add a separator after directory names, so completion can carry on into them
+    pub fn trailing_separator(mut self, trailing: bool) -> Self {
+        self.trailing_separator = trailing;
+        self
+    }
+    /// Complete relative paths from `root` rather than the current directory
+    pub fn root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.root = Some(root.into());
+        self
+    }
+    // the candidates for the word after `skip` bytes of it (eg "--file=")
+    pub(crate) fn complete_after(
+        &self,
+        ctx: &CompletionContext<'_>,
+        skip: usize,
+    ) -> Vec<Candidate> {
+        let Some(search) = ctx.prefix().get(skip..) else {
+            return Vec::new();
+        };
+        let lead = &ctx.prefix()[..skip];
+        // windows file names are not case sensitive
+        let mode = if cfg!(target_os = "windows") && ctx.mode == MatchMode::Prefix {
+            MatchMode::CaseInsensitive
+        } else {
+            ctx.mode
+        };
+        // the directory as typed and the start of the name in it
+        let (dir, name) = match search.rfind(is_separator) {
+            Some(i) => search.split_at(i + 1),
+            None => ("", search),
+        };
+        let separator = if cfg!(target_os = "windows") && dir.contains('\\') {
+            '\\'
+        } else {
+            '/'
+        };
+        let mut entries = self.entries(&self.directory(dir));
+        if name == "." || name == ".." {
+            entries.insert(0, (name.to_string(), true));
+        }
+        entries
+            .into_iter()
+            .filter(|(file, is_dir)| {
+                (self.hidden || !file.starts_with('.') || name.starts_with('.'))
+                    && match &self.filter {
+                        FsFilter::All => true,
+                        FsFilter::DirsOnly => *is_dir,
+                        FsFilter::Extensions(extensions) => {
+                            *is_dir
+                                || Path::new(file)
+                                    .extension()
+                                    .is_some_and(|e| extensions.iter().any(|x| e == x.as_str()))
+                        }
+                    }
+                    && mode.find(name, file).is_some()
+            })
+            .map(|(file, is_dir)| {
+                let mut path = format!("{}{}{}", lead, dir, file);
+                let kind = if is_dir {
+                    if self.trailing_separator {
+                        path.push(separator);
+                    }
+                    CandidateKind::Directory
+                } else {
+                    CandidateKind::File
+                };
+                Candidate::new(ctx.quote_if_needed(&path), ctx.word_range())
+                    .display(path)
+                    .kind(kind)
+            })
+            .collect()
+    }
+    // where the directory typed really is
+    fn directory(&self, dir: &str) -> PathBuf {
+        let mut expanded = if self.expand_vars {
+            expand_vars(dir)
+        } else {
+            dir.to_string()
+        };
+        if self.expand_home {
+            expanded = expand_home(&expanded);
+        }
+        let path = PathBuf::from(if expanded.is_empty() { "." } else { &expanded });
+        match &self.root {
+            Some(root) if path.is_relative() => root.join(path),
+            _ => path,
+        }
+    }
+    // the names in a directory and whether they are directories, sorted by name
+    fn entries(&self, dir: &Path) -> Vec<(String, bool)> {
+        self.fs
+            .list(dir)
+            .into_iter()
+            .map(|name| {
+                let is_dir = self.fs.is_dir(&dir.join(&name));
+                (name, is_dir)
+            })
+            // windows users expect case insensitive order
+            .sorted_by_key(|(name, _)| {
+                if cfg!(target_os = "windows") {
+                    name.to_lowercase()
+                } else {
+                    name.clone()
+                }
+            })
+            .collect()
+    }
+}
+
+impl Completer for FsCompleter {
+    fn complete(&self, ctx: &CompletionContext<'_>) -> Vec<Candidate> {
+        if ctx.arg_index() == 0 {
+            return Vec::new();
+        }
+        self.complete_after(ctx, 0)
+    }
+}
+
+fn is_separator(c: char) -> bool {
+    c == '/' || (cfg!(target_os = "windows") && c == '\\')
+}
+
+// "~" or "~/..." with the home directory
+fn expand_home(path: &str) -> String {
+    let rest = match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with(is_separator) => rest,
+        _ => return path.to_string(),
+    };
+    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"));
+    match home {
+        Some(home) => format!("{}{}", home.to_string_lossy(), rest),
+        None => path.to_string(),
+    }
+}
+
+// $VAR and ${VAR} with their values, unknown variables are left alone
+fn expand_vars(path: &str) -> String {
+    let mut out = String::new();
+    let mut rest = path;
+    while let Some(i) = rest.find('$') {
+        out.push_str(&rest[..i]);
+        let after = &rest[i + 1..];
+        let (name, len) = match after.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (&braced[..end], end + 2),
+                None => ("", 0),
+            },
+            None => {
+                let end = after
+                    .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                    .unwrap_or(after.len());
+                (&after[..end], end)
+            }
+        };
+        match std::env::var(name) {
+            Ok(value) if !name.is_empty() => out.push_str(&value),
+            _ => out.push_str(&rest[i..i + 1 + len]),
+        }
+        rest = &after[len..];
+    }
+    out.push_str(rest);
+    out
+}
+
+// what tab completes unless the app installs its own completer
+pub(crate) fn default_completer() -> Slot<dyn Completer + Send> {
+    Slot(Some(Box::new(CommandCompleter.chain(FsCompleter::new()))))
+}
+
+#[test]
+fn test_completers() {
+    let commands = vec!["help".to_string(), "history".to_string(), "cd".to_string()];
+    let complete = |line: &str| {
+        let ctx = CompletionContext::new(line, line.len(), &commands, '"', MatchMode::Prefix);
+        CommandCompleter
+            .chain(|ctx: &CompletionContext<'_>| {
+                if ctx.arg_index() == 1 && ctx.matches("red") {
+                    vec![Candidate::new("red", ctx.word_range()).description("a colour")]
+                } else {
+                    Vec::new()
+                }
+            })
+            .complete(&ctx)
+    };
+    let names = |c: Vec<Candidate>| c.into_iter().map(|c| c.replacement).collect::<Vec<_>>();
+    assert_eq!(names(complete("h")), vec!["help", "history"]);
+    assert_eq!(names(complete("")), vec!["help", "history", "cd"]);
+    assert_eq!(
+        complete("x r"),
+        vec![Candidate::new("red", 2..3).description("a colour")]
+    );
+    assert_eq!(complete("x 'r")[0].range, 2..4);
+    assert!(complete("x y r").is_empty());
+}
+
+#[test]
+fn test_fs_completer() {
+    let commands = Vec::new();
+    let line = "cat src/con";
+    let ctx = CompletionContext::new(line, line.len(), &commands, '"', MatchMode::Prefix);
+    let found = FsCompleter::new().complete(&ctx);
+    assert_eq!(
+        found
+            .iter()
+            .map(|c| c.replacement.as_str())
+            .collect::<Vec<_>>(),
+        vec!["src/console.rs"]
+    );
+    assert_eq!(found[0].range, 4..11);
+    let ctx = CompletionContext::new(line, 3, &commands, '"', MatchMode::Prefix);
+    assert!(FsCompleter::new().complete(&ctx).is_empty());
+}
+
+#[test]
+fn test_match_modes() {
+    let find = |mode: MatchMode, typed, candidate| mode.find(typed, candidate).map(|m| m.positions);
+    assert_eq!(find(MatchMode::Prefix, "ne", "net.open"), Some(vec![0, 1]));
+    assert_eq!(find(MatchMode::Prefix, "Ne", "net.open"), None);
+    assert_eq!(
+        find(MatchMode::CaseInsensitive, "Ne", "net.open"),
+        Some(vec![0, 1])
+    );
+    assert_eq!(
+        find(MatchMode::Substring, "OP", "net.open"),
+        Some(vec![4, 5])
+    );
+    assert_eq!(find(MatchMode::Substring, "x", "net.open"), None);
+    assert_eq!(find(MatchMode::Fuzzy, "no", "net.open"), Some(vec![0, 4]));
+    assert_eq!(find(MatchMode::Fuzzy, "nx", "net.open"), None);
+    // runs and word starts rank higher
+    let score = |typed, candidate| MatchMode::Fuzzy.find(typed, candidate).unwrap().score;
+    assert!(score("no", "net.open") > score("no", "nothing.else"));
+    assert!(score("ns", "net.server") > score("ns", "netstat"));
+}
+
+#[test]
+fn test_find_word() {
+    let find =
+        |mode: MatchMode, typed, candidate| mode.find_word(typed, candidate).map(|m| m.positions);
+    assert_eq!(
+        find(MatchMode::Substring, "src/o", "src/console.rs"),
+        Some(vec![0, 1, 2, 3, 5])
+    );
+    assert_eq!(find(MatchMode::Substring, "src/x", "src/console.rs"), None);
+    assert_eq!(
+        find(MatchMode::Fuzzy, "--mode=dk", "--mode=dark"),
+        Some(vec![0, 1, 2, 3, 4, 5, 6, 7, 10])
+    );
+    // without the same directory the whole word is matched
+    assert_eq!(
+        find(MatchMode::Substring, "c/c", "src/console.rs"),
+        Some(vec![2, 3, 4])
+    );
+}
+
+#[test]
+fn test_fs_completer_options() {
+    let mut fs = crate::MemoryFs::new()
+        .file(".gitignore")
+        .file("Cargo.toml")
+        .file("README.md")
+        .file("src/console.rs")
+        .file("src/lib.rs");
+    // ~ and variables expand to what the process has, when it has them
+    let home = std::env::var("HOME").ok();
+    if let Some(home) = &home {
+        fs = fs.file(Path::new(home).join("notes.txt"));
+    }
+    let manifest = std::env::var("CARGO_MANIFEST_DIR").ok();
+    if let Some(manifest) = &manifest {
+        fs = fs.file(Path::new(manifest).join("build.rs"));
+    }
+    let commands = Vec::new();
+    let complete = |completer: &FsCompleter, line: &str| {
+        let ctx = CompletionContext::new(line, line.len(), &commands, '"', MatchMode::Prefix);
+        completer
+            .complete(&ctx)
+            .into_iter()
+            .map(|c| c.replacement)
+            .collect::<Vec<_>>()
+    };
+    let fs = FsCompleter::new().fs(fs);
+    assert_eq!(complete(&fs, "cd sr"), vec!["src/"]);
+    assert_eq!(complete(&fs, "cd ./sr"), vec!["./src/"]);
+    assert!(complete(&fs, "cat ").iter().all(|p| !p.starts_with('.')));
+    assert!(complete(&fs, "cat .").contains(&".gitignore".to_string()));
+    assert!(complete(&fs.clone().hidden(true), "cat ").contains(&".gitignore".to_string()));
+    assert_eq!(
+        complete(&fs.clone().trailing_separator(false), "cd sr"),
+        vec!["src"]
+    );
+    assert_eq!(
+        complete(&fs.clone().root("src"), "cat con"),
+        vec!["console.rs"]
+    );
+    let dirs = fs.clone().filter(FsFilter::DirsOnly);
+    assert_eq!(complete(&dirs, "cd "), vec!["src/"]);
+    let toml = fs
+        .clone()
+        .filter(FsFilter::Extensions(vec!["toml".to_string()]));
+    assert_eq!(complete(&toml, "cat "), vec!["Cargo.toml", "src/"]);
+
+    assert_eq!(expand_vars("a/$NO_SUCH_VAR_"), "a/$NO_SUCH_VAR_");
+    assert_eq!(expand_home("~user/x"), "~user/x");
+    if let Some(manifest) = manifest {
+        assert_eq!(
+            complete(&fs, "cat $CARGO_MANIFEST_DIR/bu"),
+            vec!["$CARGO_MANIFEST_DIR/build.rs"]
+        );
+        assert_eq!(
+            expand_vars("${CARGO_MANIFEST_DIR}/a/$NO_SUCH_VAR_"),
+            format!("{}/a/$NO_SUCH_VAR_", manifest)
+        );
+    }
+    if let Some(home) = home {
+        assert_eq!(complete(&fs, "cat ~/no"), vec!["~/notes.txt"]);
+        assert_eq!(expand_home("~/x"), format!("{}/x", home));
+    }
+}
+
+#[test]
+fn test_fs_completer_memory() {
+    let fs = crate::MemoryFs::new()
+        .file("assets/grass.png")
+        .file("assets/gravel.png")
+        .file("assets/sounds/step.ogg")
+        .file("readme.txt");
+    let completer = FsCompleter::new().fs(fs);
+    let commands = Vec::new();
+    let complete = |line: &str| {
+        let ctx = CompletionContext::new(line, line.len(), &commands, '"', MatchMode::Prefix);
+        completer
+            .complete(&ctx)
+            .into_iter()
+            .map(|c| (c.replacement, c.kind))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        complete("load "),
+        vec![
+            ("assets/".to_string(), CandidateKind::Directory),
+            ("readme.txt".to_string(), CandidateKind::File)
+        ]
+    );
+    assert_eq!(
+        complete("load assets/gr"),
+        vec![
+            ("assets/grass.png".to_string(), CandidateKind::File),
+            ("assets/gravel.png".to_string(), CandidateKind::File)
+        ]
+    );
+    assert_eq!(
+        complete("load ./assets/s"),
+        vec![("./assets/sounds/".to_string(), CandidateKind::Directory)]
+    );
+}
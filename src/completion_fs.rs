@@ -0,0 +1,130 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Component, Path, PathBuf},
+};
+
+/// The file system [`crate::FsCompleter`] completes paths from
+///
+/// [`StdFs`], the real file system, is the default. Implement it to complete paths from
+/// somewhere else, an asset tree or the inside of an archive, or use a [`MemoryFs`].
+pub trait CompletionFs {
+    /// The names of the entries in directory `dir`, empty if it is not a directory
+    fn list(&self, dir: &Path) -> Vec<String>;
+    /// Is `path` a directory
+    fn is_dir(&self, path: &Path) -> bool;
+}
+
+/// The real file system, through `std::fs`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdFs;
+
+impl CompletionFs for StdFs {
+    fn list(&self, dir: &Path) -> Vec<String> {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return Vec::new();
+        };
+        entries
+            .flatten()
+            .map(|e| e.file_name().to_string_lossy().into_owned())
+            .collect()
+    }
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+}
+
+/// A file system that only exists in memory
+///
+/// For tests, for wasm where there is no file system, or for completing from app data.
+/// Adding a path adds the directories above it. Relative and absolute paths are separate trees.
+///
+/// ```ignore
+/// let fs = MemoryFs::new()
+///     .file("assets/textures/grass.png")
+///     .file("assets/sounds/step.ogg")
+///     .dir("saves");
+/// let completer = FsCompleter::new().fs(fs);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MemoryFs {
+    // every directory and what is in it
+    dirs: BTreeMap<PathBuf, BTreeSet<String>>,
+}
+
+impl MemoryFs {
+    /// Create an empty file system
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Add a file
+    pub fn file(mut self, path: impl AsRef<Path>) -> Self {
+        self.add(path.as_ref());
+        self
+    }
+    /// Add a directory
+    pub fn dir(mut self, path: impl AsRef<Path>) -> Self {
+        let path = normalize(path.as_ref());
+        self.add(&path);
+        self.dirs.entry(path).or_default();
+        self
+    }
+    fn add(&mut self, path: &Path) {
+        let path = normalize(path);
+        let mut child = path.as_path();
+        while let (Some(parent), Some(name)) = (child.parent(), child.file_name()) {
+            self.dirs
+                .entry(parent.to_path_buf())
+                .or_default()
+                .insert(name.to_string_lossy().into_owned());
+            child = parent;
+        }
+    }
+}
+
+impl CompletionFs for MemoryFs {
+    fn list(&self, dir: &Path) -> Vec<String> {
+        self.dirs
+            .get(&normalize(dir))
+            .map(|names| names.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+    fn is_dir(&self, path: &Path) -> bool {
+        self.dirs.contains_key(&normalize(path))
+    }
+}
+
+// drop "." and resolve "..", so "./a/../b" and "b" are the same directory
+fn normalize(path: &Path) -> PathBuf {
+    let mut normal = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normal.pop();
+            }
+            other => normal.push(other),
+        }
+    }
+    normal
+}
+
+#[test]
+fn test_memory_fs() {
+    let fs = MemoryFs::new()
+        .file("assets/grass.png")
+        .file("./assets/sounds/step.ogg")
+        .dir("saves")
+        .file("/etc/hosts");
+    assert_eq!(fs.list(Path::new(".")), vec!["assets", "saves"]);
+    assert_eq!(fs.list(Path::new("assets/")), vec!["grass.png", "sounds"]);
+    assert_eq!(
+        fs.list(Path::new("assets/sounds/..")),
+        vec!["grass.png", "sounds"]
+    );
+    assert_eq!(fs.list(Path::new("/")), vec!["etc"]);
+    assert!(fs.list(Path::new("saves")).is_empty());
+    assert!(fs.list(Path::new("nowhere")).is_empty());
+    assert!(fs.is_dir(Path::new("./saves")));
+    assert!(fs.is_dir(Path::new("assets/sounds")));
+    assert!(!fs.is_dir(Path::new("assets/grass.png")));
+}
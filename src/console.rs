@@ -1,86 +1,265 @@
-use std::{collections::VecDeque, str::Lines, sync::atomic::AtomicU16};
+use std::{collections::VecDeque, ops::Range, str::Lines, sync::atomic::AtomicU16};
 
 use egui::{
-    text::CCursorRange, Align, Context, Event, EventFilter, Id, Key, Modifiers, TextEdit, Ui,
+    text::{CCursor, CCursorRange},
+    text_edit::TextEditOutput,
+    Align, Align2, Context, Event, EventFilter, Id, Key, Layout, Margin, Modifiers, Sense,
+    TextEdit, Ui,
+};
+
+use crate::{
+    ansi::AnsiParser,
+    buffer::{OutputLine, ScrollBack},
+    completion::{default_completer, Candidate, Completer, CompletionStyle, MatchMode},
+    expand::expand_history,
+    history::{FileHistory, HistoryDuplicates, HistoryEntry, HistoryStore},
+    keymap::{Action, Keymap},
+    slot::Slot,
+    style::{ConsoleTheme, Span},
+    writer::{ConsoleWriter, Output, UiHandle, WriterChannel},
 };
 
-static SEARCH_PROMPT: &str = "(reverse-i-search) :";
-const SEARCH_PROMPT_SLOT_OFF: usize = 18;
 static INSTANCE_COUNT: AtomicU16 = AtomicU16::new(0);
 
 /// The event that was generated by the console
 ///
 ///
 pub enum ConsoleEvent {
-    /// A command was entered
+    /// A command was entered, multi-line input has the lines joined by `\n`
     Command(String),
 
+    /// Ctrl-D was pressed on an empty line
+    Eof,
+
     /// Nothing
     None,
 }
+/// How the input line is edited, see [`ConsoleBuilder::edit_mode`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+pub enum EditMode {
+    /// The readline (emacs) keys
+    #[default]
+    Emacs,
+    /// vi insert and normal modes, escape goes to normal mode
+    Vi,
+}
+
 /// Console Window  
 ///
 ///
 #[derive(Debug)]
 #[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+// state saved by an older version is missing fields, they get the defaults
+#[cfg_attr(feature = "persistence", serde(default = "ConsoleWindow::unsaved"))]
 pub struct ConsoleWindow {
+    // output lines, the input line is kept separately in `input`
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub(crate) scrollback: ScrollBack,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub(crate) input: String,
+    // has the user been prompted. The input line is always there (so it keeps
+    // the focus), anything typed before the app reprompts is kept as type ahead
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub(crate) prompting: bool,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    ansi: AnsiParser,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    theme: ConsoleTheme,
+    // output queued by ConsoleWriters
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    writers: WriterChannel,
+    // runs the built in loglevel command
+    #[cfg(feature = "log")]
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    log_handle: Option<crate::LogHandle>,
+    // runs the built in tracefilter command
+    #[cfg(feature = "tracing")]
     #[cfg_attr(feature = "persistence", serde(skip))]
-    pub(crate) text: String,
+    trace_handle: Option<crate::TraceHandle>,
     #[cfg_attr(feature = "persistence", serde(skip))]
     pub(crate) force_cursor_to_end: bool,
-    history_size: usize,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    scroll_to_bottom: bool,
+    pub(crate) history_size: usize,
     pub(crate) scrollback_size: usize,
-    command_history: VecDeque<String>,
+    #[cfg_attr(
+        feature = "persistence",
+        serde(deserialize_with = "crate::history::deserialize_entries")
+    )]
+    pub(crate) command_history: VecDeque<HistoryEntry>,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub(crate) history_cursor: Option<usize>,
+    // given to new history entries, and when the command being run started
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub(crate) history_tag: Option<String>,
     #[cfg_attr(feature = "persistence", serde(skip))]
-    history_cursor: Option<usize>,
+    pub(crate) running: Option<web_time::Instant>,
+    // ctrl-r skips commands that failed
+    search_only_successful: bool,
+    // bash style !! expansion
+    history_expansion: bool,
+    // which commands are kept out of the history
+    pub(crate) history_ignore_empty: bool,
+    pub(crate) history_ignore_space: bool,
+    pub(crate) history_duplicates: HistoryDuplicates,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub(crate) history_ignore: Slot<dyn Fn(&str) -> bool + Send>,
+    // where the history is saved as commands are run
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub(crate) history_store: Slot<dyn HistoryStore + Send>,
     pub(crate) prompt: String,
-    prompt_len: usize,
-    id: Id,
-    save_prompt: Option<String>,
+    // shown in front of the second and later lines of multi-line input
+    continuation_prompt: String,
+    // says when Enter submits and when it starts another line
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    validator: Slot<dyn Fn(&str) -> bool + Send>,
+    pub(crate) id: Id,
     #[cfg_attr(feature = "persistence", serde(skip))]
     search_partial: Option<String>,
     // enable running stuff after serde reload
     #[cfg_attr(feature = "persistence", serde(skip))]
     init_done: bool,
 
+    // the cursor (byte offset into input) when the keys were handled
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub(crate) cursor: usize,
+    // put the cursor here (byte offset) next frame
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub(crate) move_cursor_to: Option<usize>,
+
+    // fish style suggestions, the rest of the latest history line that starts with the input
+    autosuggest: bool,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    suggestion: Option<String>,
+
+    // killed text for yanking back, and what the last yank put where
+    keymap: Keymap,
+    pub(crate) edit_mode: EditMode,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub(crate) kill_ring: Vec<String>,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub(crate) yanked: Option<(Range<usize>, usize)>,
+    // vi normal mode and the operator waiting for a motion
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub(crate) vi_normal: bool,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub(crate) vi_pending: Option<char>,
+    // undo and redo steps (the line and cursor), the line as last seen, and was its last change typing
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub(crate) undo: Vec<(String, usize)>,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub(crate) redo: Vec<(String, usize)>,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub(crate) undo_current: (String, usize),
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub(crate) undo_typing: bool,
+
     // tab completion
     #[cfg_attr(feature = "persistence", serde(skip))]
-    pub(crate) tab_string: String,
+    pub(crate) completer: Slot<dyn Completer + Send>,
+    // the line and cursor as they were when tab was pressed, and what the completer offered
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub(crate) tab_line: String,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub(crate) tab_cursor: usize,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub(crate) tab_candidates: Vec<Candidate>,
+    // bash style, the candidates are waiting for a second tab to list them
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub(crate) tab_pending: bool,
+    // the popup, the candidates still matching and the selected one
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub(crate) tab_popup: bool,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub(crate) tab_start: usize,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub(crate) tab_visible: Vec<(usize, Vec<usize>)>,
     #[cfg_attr(feature = "persistence", serde(skip))]
     pub(crate) tab_nth: usize,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub(crate) tab_scroll: bool,
     pub(crate) tab_quote: char,
+    pub(crate) tab_command_table: Vec<String>,
     #[cfg_attr(feature = "persistence", serde(skip))]
-    pub(crate) tab_quoted: bool,
+    pub(crate) match_mode: MatchMode,
     #[cfg_attr(feature = "persistence", serde(skip))]
-    pub(crate) tab_offset: usize,
-    pub(crate) tab_command_table: Vec<String>,
+    pub(crate) completion_style: CompletionStyle,
 }
 
 impl ConsoleWindow {
+    #[cfg(feature = "persistence")]
+    fn unsaved() -> Self {
+        ConsoleBuilder::new().build()
+    }
     pub(crate) fn new(prompt: &str) -> Self {
         Self {
-            text: String::new(),
+            scrollback: ScrollBack::default(),
+            input: String::new(),
+            prompting: false,
+            ansi: AnsiParser::default(),
+            theme: ConsoleTheme::default(),
+            writers: WriterChannel::default(),
+            #[cfg(feature = "log")]
+            log_handle: None,
+            #[cfg(feature = "tracing")]
+            trace_handle: None,
             force_cursor_to_end: false,
+            scroll_to_bottom: false,
             command_history: VecDeque::new(),
             history_cursor: None,
+            history_tag: None,
+            running: None,
+            search_only_successful: false,
+            history_expansion: false,
+            history_ignore_empty: false,
+            history_ignore_space: false,
+            history_duplicates: HistoryDuplicates::default(),
+            history_ignore: Slot::default(),
+            history_store: Slot::default(),
             history_size: 100,
             scrollback_size: 1000,
             prompt: prompt.to_string(),
-            prompt_len: prompt.chars().count(),
+            continuation_prompt: ".. ".to_string(),
+            validator: Slot::default(),
             id: Id::new(format!(
                 "console_text_{}",
                 INSTANCE_COUNT.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
             )),
-            save_prompt: None,
             search_partial: None,
             init_done: false,
 
-            tab_string: String::new(),
+            cursor: 0,
+            move_cursor_to: None,
+
+            autosuggest: true,
+            suggestion: None,
+
+            keymap: Keymap::default(),
+            edit_mode: EditMode::default(),
+            kill_ring: Vec::new(),
+            yanked: None,
+            vi_normal: false,
+            vi_pending: None,
+            undo: Vec::new(),
+            redo: Vec::new(),
+            undo_current: (String::new(), 0),
+            undo_typing: false,
+
+            completer: default_completer(),
+            tab_line: String::new(),
+            tab_cursor: 0,
+            tab_candidates: Vec::new(),
+            tab_pending: false,
+            tab_popup: false,
+            tab_start: 0,
+            tab_visible: Vec::new(),
             tab_nth: 0,
+            tab_scroll: false,
             tab_quote: '"',
-            tab_quoted: false,
-            tab_offset: usize::MAX,
             tab_command_table: Vec::new(),
+            match_mode: MatchMode::default(),
+            completion_style: CompletionStyle::default(),
         }
     }
     /// Draw the console window
@@ -90,43 +269,39 @@ impl ConsoleWindow {
     /// # Returns
     /// * `ConsoleEvent` - the event that was generated by the console
     ///
-
     pub fn draw(&mut self, ui: &mut Ui) -> ConsoleEvent {
         if !self.init_done {
             self.init_done = true;
-            if let Some(prompt) = &self.save_prompt {
-                self.prompt.clone_from(prompt);
-                self.save_prompt = None;
-            }
-            self.draw_prompt();
+            self.prompting = true;
+            // writers can now ask for a repaint
+            self.writers.ui.get_or_init(|| UiHandle {
+                ctx: ui.ctx().clone(),
+                thread: std::thread::current().id(),
+            });
         }
+        self.drain_writers();
         // do we need to handle keyboard events?
         let msg = if ui.ctx().memory(|mem| mem.has_focus(self.id)) {
             self.handle_kb(ui.ctx())
         } else {
             ConsoleEvent::None
         };
-        {
-            let text_len = self.text.len();
-            self.ui(ui);
-
-            // did somebody type?
-            if self.text.len() != text_len {
-                // yes - need to update partial search?
-                if self.search_partial.is_some() {
-                    self.search_partial = Some(self.get_search_text().to_string());
-                    self.prompt = SEARCH_PROMPT.to_string();
-                    self.prompt.insert_str(
-                        SEARCH_PROMPT_SLOT_OFF + 1,
-                        self.search_partial.as_ref().unwrap(),
-                    );
-                    self.history_cursor = None;
-                    self.history_back();
-                }
-                self.tab_string.clear();
-                self.tab_nth = 0;
+        // built in commands are dealt with here, the app never sees them
+        let msg = match msg {
+            ConsoleEvent::Command(command) if self.run_builtin(&command) => {
+                self.prompt();
+                ConsoleEvent::None
             }
+            msg => msg,
+        };
+        // the completion popup goes when the console loses focus
+        if self.tab_popup && !ui.ctx().memory(|mem| mem.has_focus(self.id)) {
+            self.tab_close();
         }
+        // keep the prompt up for the frame in which a command is returned,
+        // the app normally reprompts straight away
+        let show_prompt = self.prompting || matches!(msg, ConsoleEvent::Command(_));
+        self.ui(ui, show_prompt);
 
         // this is all so that we get the escape key (to exit search)
         let event_filter = EventFilter {
@@ -148,19 +323,155 @@ impl ConsoleWindow {
     ///
     /// Note that you can call this without the user having typed anything.
     ///
+    /// ANSI SGR escape sequences (colours, bold, italic, underline) are rendered,
+    /// all other escape sequences are stripped.
+    ///
     pub fn write(&mut self, data: &str) {
-        self.text.push_str(&format!("\n{}", data));
-        self.truncate_scroll_back();
-        self.force_cursor_to_end = true;
+        let data = data.strip_suffix('\n').unwrap_or(data);
+        for line in data.split('\n') {
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            let mut out = OutputLine::default();
+            out.styles = self.ansi.parse(line, &mut out.text);
+            self.scrollback.push(out, self.scrollback_size);
+        }
+    }
+
+    /// Write a line made up of styled spans to the console
+    /// # Arguments
+    /// * `spans` - the pieces of the line, each with its own style
+    ///
+    /// The span text is written as is, escape sequences are not interpreted.
+    ///
+    pub fn write_styled(&mut self, spans: &[Span]) {
+        let mut out = OutputLine::default();
+        for span in spans {
+            let mut pieces = span.text.split('\n');
+            out.push_str(pieces.next().unwrap_or(""), span.style);
+            // a newline inside a span starts a new output line
+            for piece in pieces {
+                self.scrollback
+                    .push(std::mem::take(&mut out), self.scrollback_size);
+                out.push_str(piece, span.style);
+            }
+        }
+        self.scrollback.push(out, self.scrollback_size);
+    }
+
+    /// Write a line in the theme's error style
+    /// # Arguments
+    /// * `data` - the string to write
+    ///
+    pub fn write_error(&mut self, data: &str) {
+        let style = self.theme.error;
+        self.write_styled(&[Span::new(data).style(style)]);
+    }
+
+    /// Write a line in the theme's warning style
+    /// # Arguments
+    /// * `data` - the string to write
+    ///
+    pub fn write_warning(&mut self, data: &str) {
+        let style = self.theme.warning;
+        self.write_styled(&[Span::new(data).style(style)]);
+    }
+
+    /// Write a line in the theme's info style
+    /// # Arguments
+    /// * `data` - the string to write
+    ///
+    pub fn write_info(&mut self, data: &str) {
+        let style = self.theme.info;
+        self.write_styled(&[Span::new(data).style(style)]);
+    }
+
+    /// Get a handle that can write to this console from other threads
+    /// # Returns
+    /// * `ConsoleWriter` - the writer
+    ///
+    pub fn writer(&self) -> ConsoleWriter {
+        ConsoleWriter::new(&self.writers)
+    }
+
+    /// Enable the built in `loglevel` command for changing the [`crate::ConsoleLogger`] levels
+    /// # Arguments
+    /// * `handle` - the handle returned by [`crate::ConsoleLogger::init`]
+    ///
+    /// `loglevel` shows the levels, `loglevel <level> [target]` changes one.
+    ///
+    #[cfg(feature = "log")]
+    pub fn log_command(&mut self, handle: crate::LogHandle) {
+        self.log_handle = Some(handle);
+        if !self.tab_command_table.iter().any(|c| c == "loglevel") {
+            self.tab_command_table.push("loglevel".to_string());
+        }
+    }
+
+    /// Enable the built in `tracefilter` command for changing the [`crate::ConsoleLayer`] filter
+    /// # Arguments
+    /// * `handle` - from [`crate::ConsoleLayer::handle`]
+    ///
+    /// `tracefilter` shows the filter, `tracefilter <directive>` replaces it.
+    ///
+    #[cfg(feature = "tracing")]
+    pub fn trace_command(&mut self, handle: crate::TraceHandle) {
+        self.trace_handle = Some(handle);
+        if !self.tab_command_table.iter().any(|c| c == "tracefilter") {
+            self.tab_command_table.push("tracefilter".to_string());
+        }
+    }
+
+    // returns true if the command was a built in one (and so has been run)
+    fn run_builtin(&mut self, line: &str) -> bool {
+        let args: Vec<&str> = line.split_whitespace().collect();
+        match args.split_first() {
+            #[cfg(feature = "log")]
+            Some((&"loglevel", args)) if self.log_handle.is_some() => {
+                match self.log_handle.as_ref().unwrap().command(args) {
+                    Ok(out) => self.write(&out),
+                    Err(err) => {
+                        self.write_error(&err);
+                        self.command_status(false);
+                    }
+                }
+                true
+            }
+            #[cfg(feature = "tracing")]
+            Some((&"tracefilter", args)) if self.trace_handle.is_some() => {
+                match self.trace_handle.as_ref().unwrap().command(args) {
+                    Ok(out) => self.write(&out),
+                    Err(err) => {
+                        self.write_error(&err);
+                        self.command_status(false);
+                    }
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // move output sent by writers into the scrollback
+    pub(crate) fn drain_writers(&mut self) {
+        while let Ok(output) = self.writers.receiver.try_recv() {
+            match output {
+                Output::Text(data) => self.write(&data),
+                Output::Styled(spans) => self.write_styled(&spans),
+                Output::Error(data) => self.write_error(&data),
+                Output::Warning(data) => self.write_warning(&data),
+                Output::Info(data) => self.write_info(&data),
+            }
+        }
     }
 
     /// Loads the history from an iterator of strings
     /// # Arguments
     /// * `history` - an iterator of strings
     ///
+    /// Each line is one entry, so a multi-line command comes back as separate entries.
+    ///
     ///
     pub fn load_history(&mut self, history: Lines<'_>) {
-        self.command_history = history.into_iter().map(|s| s.to_string()).collect();
+        self.command_history = history.into_iter().map(HistoryEntry::new).collect();
         self.history_cursor = None;
     }
 
@@ -170,174 +481,336 @@ impl ConsoleWindow {
     ///
     ///     
     pub fn get_history(&self) -> VecDeque<String> {
-        self.command_history.clone()
+        self.command_history
+            .iter()
+            .map(|entry| entry.command.clone())
+            .collect()
+    }
+    /// Get the history with when each command ran, how long it took and whether it worked
+    /// # Returns
+    /// * `&VecDeque<HistoryEntry>` - the history, oldest first
+    ///
+    pub fn history_entries(&self) -> &VecDeque<HistoryEntry> {
+        &self.command_history
     }
-    /// Clear the history of the console
+    /// Clear the history of the console, and the history store if there is one
     ///
     pub fn clear_history(&mut self) {
         self.command_history.clear();
         self.history_cursor = None;
+        self.running = None;
+        self.history_clear();
+    }
+    /// Say whether the command that is running worked, it is kept in its [`HistoryEntry`]
+    /// # Arguments
+    /// * `success` - did it work
+    ///
+    /// Call it before [`ConsoleWindow::prompt`], the command is finished when the app prompts again.
+    /// [`crate::CommandRegistry`] and [`crate::ClapCommands`] call it for you.
+    ///
+    pub fn command_status(&mut self, success: bool) {
+        if self.running.is_some() {
+            if let Some(entry) = self.command_history.back_mut() {
+                entry.success = Some(success);
+            }
+        }
+    }
+    /// Set the tag given to the commands run from now on, for example the working directory
+    /// # Arguments
+    /// * `tag` - the tag, or None for no tag
+    ///
+    pub fn set_history_tag(&mut self, tag: Option<String>) {
+        self.history_tag = tag;
     }
 
     /// Clear the console
     pub fn clear(&mut self) {
-        self.text.clear();
-        self.force_cursor_to_end = false;
+        self.scrollback.clear();
     }
     /// Prompt the user for input
+    ///
+    /// If the user is already being prompted this leaves the input line alone.
+    /// Prompting after a command finishes its history entry, and saves it to the history store.
     pub fn prompt(&mut self) {
-        self.draw_prompt();
+        self.prompting = true;
+        self.history_finish();
     }
     /// get mut ref to tab completion table for commands
     pub fn command_table_mut(&mut self) -> &mut Vec<String> {
         &mut self.tab_command_table
     }
-
-    fn cursor_at_end(&self) -> CCursorRange {
-        egui::text::CCursorRange::one(egui::text::CCursor::new(self.text.chars().count()))
+    /// Replace the tab completer
+    /// # Arguments
+    /// * `completer` - see [`crate::ConsoleBuilder::completer`]
+    ///
+    pub fn set_completer(&mut self, completer: impl Completer + Send + 'static) {
+        self.completer = Slot(Some(Box::new(completer)));
+        self.tab_close();
+    }
+    /// Replace the history store, the history is reloaded from it
+    /// # Arguments
+    /// * `store` - see [`crate::ConsoleBuilder::history_store`]
+    ///
+    pub fn set_history_store(&mut self, store: impl HistoryStore + Send + 'static) {
+        self.history_store = Slot(Some(Box::new(store)));
+        self.history_load();
+    }
+    /// Replace the input validator
+    /// # Arguments
+    /// * `validator` - see [`crate::ConsoleBuilder::validator`]
+    ///
+    pub fn set_validator(&mut self, validator: impl Fn(&str) -> bool + Send + 'static) {
+        self.validator = Slot(Some(Box::new(validator)));
     }
-    fn cursor_at(&self, loc: usize) -> CCursorRange {
-        if loc >= self.text.chars().count() {
-            return self.cursor_at_end();
-        }
-        egui::text::CCursorRange::one(egui::text::CCursor::new(loc))
-    }
-    fn ui(&mut self, ui: &mut egui::Ui) {
-        egui::ScrollArea::both().show(ui, |ui| {
-            ui.add_sized(ui.available_size(), |ui: &mut Ui| {
-                let widget = egui::TextEdit::multiline(&mut self.text)
-                    .font(egui::TextStyle::Monospace)
-                    .frame(false)
-                    .code_editor()
-                    .lock_focus(true)
-                    .desired_width(f32::INFINITY)
-                    .id(self.id);
-                let output = widget.show(ui);
-                let mut new_cursor = None;
-
-                // fix up cursor position
-                // different logic depending on normal vs search mode
-                // scroll, mouse move etc
-                // cursor might not be in a good location
-
-                match self.search_partial {
-                    Some(_) => {
-                        if let Some(cursor) = output.state.cursor.char_range() {
-                            let last_off = self.last_line_offset();
-                            if cursor.primary.index < (last_off + SEARCH_PROMPT_SLOT_OFF + 1) {
-                                new_cursor =
-                                    Some(self.cursor_at(last_off + SEARCH_PROMPT_SLOT_OFF + 1));
-                            } else {
-                                let search_text = self.get_search_text();
-                                if cursor.primary.index
-                                    > (last_off + SEARCH_PROMPT.len() + search_text.len())
-                                {
-                                    new_cursor = Some(self.cursor_at(
-                                        last_off + SEARCH_PROMPT_SLOT_OFF + search_text.len() + 1,
-                                    ));
-                                }
-                            }
-                        }
-                    }
-                    None => {
-                        if let Some(cursor) = output.state.cursor.char_range() {
-                            let last_off = self.last_line_offset();
-                            if cursor.primary.index < last_off + self.prompt_len - 1 {
-                                new_cursor = Some(self.cursor_at_end());
-                            }
-                        }
 
-                        // we need a new line (user pressed enter)
-                        if self.force_cursor_to_end {
-                            new_cursor = Some(self.cursor_at_end());
-                            self.force_cursor_to_end = false;
-                        }
-                    }
-                };
+    fn ui(&mut self, ui: &mut egui::Ui, show_prompt: bool) {
+        let id = self.id;
+        // every output line is one row of monospace text, which lets the
+        // scroll area lay out only the rows that are visible
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+        let total_rows = self.scrollback.len();
 
-                if new_cursor.is_some() {
-                    let text_edit_id = output.response.id;
+        // input line pinned to the bottom, output fills the rest above it
+        ui.with_layout(Layout::bottom_up(Align::LEFT), |ui| {
+            ui.spacing_mut().item_spacing.y = 0.0;
+            self.input_ui(ui, show_prompt);
 
-                    if let Some(mut state) = TextEdit::load_state(ui.ctx(), text_edit_id) {
-                        state.cursor.set_char_range(new_cursor);
-                        state.store(ui.ctx(), text_edit_id);
-                    }
-                    ui.scroll_to_cursor(Some(Align::BOTTOM));
+            ui.with_layout(Layout::top_down(Align::LEFT), |ui| {
+                // clicking in empty space goes to the input line. Sensed before the
+                // output is added so that the labels stay selectable
+                let background = ui.interact(
+                    ui.available_rect_before_wrap(),
+                    id.with("background"),
+                    Sense::click(),
+                );
+                if background.clicked() {
+                    ui.memory_mut(|mem| mem.request_focus(id));
+                }
+
+                let mut scroll_area = egui::ScrollArea::both()
+                    .id_source(id.with("scroll"))
+                    .auto_shrink(false)
+                    .stick_to_bottom(true);
+                if self.scroll_to_bottom {
+                    // gets clamped to the real bottom
+                    scroll_area =
+                        scroll_area.vertical_scroll_offset(row_height * total_rows as f32);
+                    self.scroll_to_bottom = false;
                 }
-                output.response
+                scroll_area.show_rows(ui, row_height, total_rows, |ui, rows| {
+                    for line in self.scrollback.range(rows) {
+                        ui.add(egui::Label::new(line.layout_job(ui)).extend());
+                    }
+                });
             });
         });
     }
 
-    pub(crate) fn get_last_line(&self) -> &str {
-        self.text
-            .lines()
-            .last()
-            .unwrap_or("")
-            .strip_prefix(&self.prompt)
-            .unwrap_or("")
-    }
-    fn truncate_scroll_back(&mut self) {
-        let line_count = self.text.lines().count();
-        if line_count < self.scrollback_size {
-            return;
+    // the prompt followed by the editable input line
+    fn input_ui(&mut self, ui: &mut Ui, show_prompt: bool) {
+        // in search mode the prompt shows what we are looking for and the
+        // input line shows the match
+        let mut prompt = match &self.search_partial {
+            Some(search) => format!("(reverse-i-search)`{}': ", search),
+            None if show_prompt => format!("{}{}", self.vi_indicator(), self.prompt),
+            None => String::new(),
+        };
+        // the rest of a multi-line input gets continuation prompts
+        for _ in self.input.matches('\n') {
+            prompt.push('\n');
+            if show_prompt {
+                prompt.push_str(&self.continuation_prompt);
+            }
         }
-        let mut scrollback = String::with_capacity(self.text.len());
 
-        for (i, line) in self.text.lines().enumerate() {
-            if i > line_count - self.scrollback_size {
-                scrollback.push_str(line);
-                scrollback.push('\n');
+        if self.force_cursor_to_end {
+            if let Some(mut state) = TextEdit::load_state(ui.ctx(), self.id) {
+                let end = CCursor::new(self.input.chars().count());
+                state.cursor.set_char_range(Some(CCursorRange::one(end)));
+                state.store(ui.ctx(), self.id);
+            }
+        } else if let Some(pos) = self.move_cursor_to {
+            if let Some(mut state) = TextEdit::load_state(ui.ctx(), self.id) {
+                let pos = CCursor::new(self.input[..pos.min(self.input.len())].chars().count());
+                state.cursor.set_char_range(Some(CCursorRange::one(pos)));
+                state.store(ui.ctx(), self.id);
             }
         }
-        self.text = scrollback;
+
+        let output = ui.horizontal(|ui| {
+            ui.spacing_mut().item_spacing.x = 0.0;
+            ui.add(egui::Label::new(OutputLine::plain(prompt).layout_job(ui)).extend());
+            TextEdit::multiline(&mut self.input)
+                .font(egui::TextStyle::Monospace)
+                .frame(false)
+                .margin(Margin::ZERO)
+                .desired_rows(1)
+                .code_editor()
+                .lock_focus(true)
+                .desired_width(f32::INFINITY)
+                .id(self.id)
+                .show(ui)
+        });
+        self.force_cursor_to_end = false;
+        self.move_cursor_to = None;
+        if self.tab_popup {
+            self.tab_popup_ui(ui, &output.inner);
+        }
+        self.suggestion_ui(ui, &output.inner);
     }
-    fn get_search_text(&self) -> &str {
-        let last = self.text.lines().last().unwrap_or("");
-        let mut iter = last.char_indices();
-        let (start, _) = iter.nth(SEARCH_PROMPT_SLOT_OFF + 1).unwrap_or((0, ' '));
-        for (end, ch) in iter {
-            // TODO - this will fail if the search text contains ':'
-            if ch == ':' {
-                return &last[start..end];
-            }
+
+    // work out the suggestion and draw it greyed out after the cursor, it is not part of the input
+    fn suggestion_ui(&mut self, ui: &mut Ui, output: &TextEditOutput) {
+        let at_end = output.cursor_range.is_some_and(|range| {
+            range.is_empty() && range.primary.ccursor.index == self.input.chars().count()
+        });
+        self.suggestion = None;
+        if !self.autosuggest
+            || !at_end
+            || self.input.is_empty()
+            || self.tab_popup
+            || self.search_partial.is_some()
+            || !output.response.has_focus()
+        {
+            return;
+        }
+        self.suggestion = self
+            .command_history
+            .iter()
+            .rev()
+            .map(|entry| &entry.command)
+            .find(|line| line.len() > self.input.len() && line.starts_with(self.input.as_str()))
+            .map(|line| line[self.input.len()..].to_string());
+        if let Some(suggestion) = &self.suggestion {
+            let end = output.galley.pos_from_cursor(&output.galley.end());
+            ui.painter().text(
+                output.galley_pos + end.min.to_vec2(),
+                Align2::LEFT_TOP,
+                suggestion,
+                egui::TextStyle::Monospace.resolve(ui.style()),
+                ui.visuals().weak_text_color(),
+            );
         }
-        ""
     }
+    // take the suggestion, or just its next word
+    fn accept_suggestion(&mut self, whole: bool) -> bool {
+        let Some(suggestion) = self.suggestion.take() else {
+            return false;
+        };
+        if self.cursor != self.input.len() {
+            return false;
+        }
+        let take = if whole {
+            suggestion.len()
+        } else {
+            let word = suggestion.len() - suggestion.trim_start().len();
+            suggestion[word..]
+                .find(char::is_whitespace)
+                .map_or(suggestion.len(), |end| word + end)
+        };
+        self.input.push_str(&suggestion[..take]);
+        self.force_cursor_to_end = true;
+        true
+    }
+
     fn consume_key(ctx: &Context, modifiers: Modifiers, logical_key: Key) {
         ctx.input_mut(|inp| inp.consume_key(modifiers, logical_key));
     }
 
-    fn handle_key(
-        &mut self,
-        key: &Key,
-        modifiers: Modifiers,
-        cursor: usize,
-    ) -> (bool, Option<String>) {
-        // return value is (consume_key, command)
+    fn handle_key(&mut self, key: &Key, modifiers: Modifiers) -> (bool, Option<ConsoleEvent>) {
+        // return value is (consume_key, event)
+        let action = self.keymap.action(modifiers, *key);
+
+        // the completion popup gets first go at the keys
+        if self.tab_popup {
+            match action {
+                Some(Action::Complete) | Some(Action::HistoryNext) => {
+                    self.tab_move(true);
+                    return (true, None);
+                }
+                Some(Action::CompleteBack) | Some(Action::HistoryPrev) => {
+                    self.tab_move(false);
+                    return (true, None);
+                }
+                Some(Action::Submit) => {
+                    self.tab_accept();
+                    return (true, None);
+                }
+                Some(Action::CancelSearch) => {
+                    self.tab_close();
+                    return (true, None);
+                }
+                _ => {}
+            }
+        }
+
+        if self.edit_mode == EditMode::Vi && self.search_partial.is_none() {
+            if let Some(consumed) = self.vi_key(key, modifiers) {
+                return (consumed, None);
+            }
+        }
 
-        let return_value = match (modifiers, key) {
-            (Modifiers::NONE, Key::ArrowDown) => {
+        if let Some(action) = action {
+            return self.run_action(action);
+        }
+
+        match (modifiers, key) {
+            // in search mode moving the cursor drops out of search, leaving
+            // the match to be edited
+            (Modifiers::NONE, Key::ArrowLeft)
+            | (Modifiers::NONE, Key::ArrowRight)
+            | (Modifiers::NONE, Key::Home)
+            | (Modifiers::NONE, Key::End)
+            | (Modifiers::NONE, Key::Delete) => {
+                if self.search_partial.is_some() {
+                    self.exit_search_mode()
+                };
+                (false, None)
+            }
+            (Modifiers::NONE, Key::Backspace) => {
+                // in search mode backspace edits the search text
+                if let Some(search) = &mut self.search_partial {
+                    search.pop();
+                    self.history_cursor = None;
+                    self.history_back();
+                    return (true, None);
+                }
+                (false, None)
+            }
+            _ => (false, None),
+        }
+    }
+
+    pub(crate) fn run_action(&mut self, action: Action) -> (bool, Option<ConsoleEvent>) {
+        match action {
+            Action::HistoryNext => {
                 // down arrow only means something if we are in search mode
                 if self.search_partial.is_some() {
                     self.exit_search_mode()
+                } else if self.input[self.cursor.min(self.input.len())..].contains('\n') {
+                    // in multi-line input the arrows move between lines first
+                    return (false, None);
                 };
                 if let Some(mut hc) = self.history_cursor {
-                    let last = self.get_last_line();
-                    self.text = self.text.strip_suffix(last).unwrap_or("").to_string();
+                    self.input.clear();
                     if hc == self.command_history.len() - 1 {
                         self.history_cursor = None;
                     } else {
                         if hc < self.command_history.len() - 1 {
                             hc += 1;
-                            self.text.push_str(self.command_history[hc].as_str());
+                            self.input.push_str(&self.command_history[hc].command);
                         }
                         self.history_cursor = Some(hc);
                     }
+                    self.force_cursor_to_end = true;
                 }
-                (true, None)
             }
-            (Modifiers::NONE, Key::ArrowUp) => {
+            Action::HistoryPrev => {
+                if self.search_partial.is_none()
+                    && self.input[..self.cursor.min(self.input.len())].contains('\n')
+                {
+                    return (false, None);
+                }
                 if self.command_history.is_empty() {
                     return (true, None);
                 }
@@ -346,104 +819,173 @@ impl ConsoleWindow {
                 };
 
                 self.history_back();
-                (true, None)
             }
-            (Modifiers::NONE, Key::Enter) => {
-                let last = self.get_last_line().to_string();
+            Action::Submit => {
+                // the app is still busy with the last command
+                if !self.prompting {
+                    return (true, None);
+                }
                 if self.search_partial.is_some() {
-                    self.exit_search_mode()
+                    self.exit_search_mode();
+                    self.cursor = self.input.len();
                 };
-                if self.command_history.len() >= self.history_size {
-                    self.command_history.pop_front();
-                }
-                self.command_history.push_back(last.clone());
-
-                self.force_cursor_to_end = true;
-                self.history_cursor = None;
-                self.truncate_scroll_back();
-                (true, Some(last))
-            }
-
-            // in search mode the cursor is constrained to the inside of the
-            // search prompt. In mormal mode the cursor is constrained to the
-            // right of the prompt
-            (Modifiers::NONE, Key::Delete) => {
-                if let Some(search_partial) = &self.search_partial {
-                    let last_off = self.last_line_offset();
-                    if cursor > (last_off + SEARCH_PROMPT.len() - 2 + search_partial.len()) {
-                        return (true, None);
-                    }
+                // not finished yet, carry on with another line
+                let complete = self
+                    .validator
+                    .0
+                    .as_ref()
+                    .map_or(true, |validator| validator(&self.input));
+                if !complete {
+                    self.insert_newline();
+                    return (true, None);
                 }
-                (false, None)
-            }
-            (Modifiers::NONE, Key::ArrowRight) => {
-                // nothing to do in normal mode. In search mode we need to
-                // constrain the cursor to the search prompt
-                if let Some(search_partial) = &self.search_partial {
-                    let last_off = self.last_line_offset();
+                let mut last = std::mem::take(&mut self.input);
 
-                    if cursor > (last_off + SEARCH_PROMPT.len() - 2 + search_partial.len()) {
-                        return (true, None);
-                    }
+                // echo the command into the scrollback
+                let mut prompt = &self.prompt;
+                for line in last.split('\n') {
+                    self.scrollback.push(
+                        OutputLine::plain(format!("{}{}", prompt, line)),
+                        self.scrollback_size,
+                    );
+                    prompt = &self.continuation_prompt;
                 }
-                (false, None)
-            }
-            (Modifiers::NONE, Key::ArrowLeft) | (Modifiers::NONE, Key::Backspace) => {
-                // in either mode dont allow motion (or deleting) into prompt
+                self.scroll_to_bottom = true;
+                self.history_cursor = None;
+                // a new line starts in vi insert mode, with nothing to undo
+                self.vi_normal = false;
+                self.undo_reset();
 
-                let last_off = self.last_line_offset();
-                match self.search_partial {
-                    Some(_) => {
-                        if cursor < (last_off + SEARCH_PROMPT_SLOT_OFF + 2) {
-                            return (true, None);
+                // !! and friends, the expanded command is shown and run (and goes in the history)
+                if self.history_expansion {
+                    match expand_history(&last, &self.command_history) {
+                        Ok(Some(expanded)) => {
+                            self.write(&expanded);
+                            last = expanded;
                         }
-                    }
-                    None => {
-                        if cursor < (last_off + self.prompt.len() + 1) {
+                        Ok(None) => {}
+                        Err(err) => {
+                            self.write_error(&err);
                             return (true, None);
                         }
                     }
                 }
-
-                (false, None)
+                // wait for the app to reprompt
+                self.history_start(&last);
+                self.prompting = false;
+                return (true, Some(ConsoleEvent::Command(last)));
             }
-            (Modifiers::NONE, Key::Escape) => {
+            Action::CancelSearch => {
                 if self.search_partial.is_some() {
                     self.exit_search_mode()
                 };
                 self.history_cursor = None;
-                (true, None)
-            }
-
-            // ctrl-r reverse search history
-            (
-                Modifiers {
-                    alt: false,
-                    ctrl: true,
-                    shift: false,
-                    mac_cmd: false,
-                    command: true,
-                },
-                Key::R,
-            ) => {
+            }
+            Action::ReverseSearch => {
                 if self.search_partial.is_none() {
-                    self.search_partial = Some(String::new());
                     self.enter_search_mode();
                 } else {
                     self.history_back();
                 }
-                (true, None)
             }
-            (Modifiers::NONE, Key::Tab) => {
-                // off to tab completion land
-                self.tab_complete();
-                (true, None)
+            Action::Complete | Action::CompleteBack => {
+                if self.search_partial.is_some() {
+                    self.exit_search_mode()
+                };
+                // off to tab completion land, backwards starts from the last candidate
+                self.tab_complete(action == Action::Complete);
+            }
+            Action::ClearScreen => self.clear(),
+            Action::Newline => {
+                self.exit_search_for_edit();
+                self.insert_newline();
             }
 
-            _ => (false, None),
-        };
+            // readline editing
+            Action::LineStart => {
+                self.exit_search_for_edit();
+                self.move_to(0);
+            }
+            Action::LineEnd => {
+                self.exit_search_for_edit();
+                if !self.accept_suggestion(true) {
+                    self.move_to(self.input.len());
+                }
+            }
+            Action::WordBack => {
+                self.exit_search_for_edit();
+                self.word_back();
+            }
+            Action::WordForward => {
+                self.exit_search_for_edit();
+                self.word_forward();
+            }
+            Action::KillToEnd => {
+                self.exit_search_for_edit();
+                self.kill_to_end();
+            }
+            Action::KillToStart => {
+                self.exit_search_for_edit();
+                self.kill_to_start();
+            }
+            Action::KillWordBack => {
+                self.exit_search_for_edit();
+                self.kill_word_back();
+            }
+            Action::Yank => {
+                self.exit_search_for_edit();
+                self.yank();
+            }
+            Action::YankPop => self.yank_pop(),
+            Action::Undo => {
+                self.exit_search_for_edit();
+                self.undo();
+            }
+            Action::Redo => {
+                self.exit_search_for_edit();
+                self.redo();
+            }
+            Action::Transpose => {
+                self.exit_search_for_edit();
+                self.transpose();
+            }
+            Action::DeleteOrEof => {
+                if self.input.is_empty() {
+                    return (true, Some(ConsoleEvent::Eof));
+                }
+                self.exit_search_for_edit();
+                self.delete_char();
+            }
+
+            // with no suggestion the key does what it normally does
+            Action::AcceptSuggestion | Action::AcceptSuggestionWord => {
+                if !self.accept_suggestion(action == Action::AcceptSuggestion) {
+                    if self.search_partial.is_some() {
+                        self.exit_search_mode()
+                    };
+                    return (false, None);
+                }
+            }
+            Action::Unbound => return (false, None),
+        }
+        (true, None)
+    }
 
-        return_value
+    // editing the line leaves search mode with the match as the line
+    fn exit_search_for_edit(&mut self) {
+        if self.search_partial.is_some() {
+            self.exit_search_mode();
+            self.cursor = self.input.len();
+        }
+    }
+
+    // typing while in search mode goes to the search text
+    fn search_type(&mut self, text: &str) {
+        if let Some(search) = &mut self.search_partial {
+            search.push_str(text);
+            self.history_cursor = None;
+            self.history_back();
+        }
     }
 
     fn history_back(&mut self) {
@@ -461,14 +1003,17 @@ impl ConsoleWindow {
                         break;
                     }
 
-                    if self.command_history[i].contains(search) {
-                        hist_line = self.command_history[i].clone();
+                    let entry = &self.command_history[i];
+                    if entry.command.contains(search.as_str())
+                        && !(self.search_only_successful && entry.success == Some(false))
+                    {
+                        hist_line = entry.command.clone();
                         self.history_cursor = Some(i);
                         break;
                     }
                 }
                 None => {
-                    hist_line = self.command_history[i].clone();
+                    hist_line = self.command_history[i].command.clone();
                     self.history_cursor = Some(i);
                     break;
                 }
@@ -476,79 +1021,79 @@ impl ConsoleWindow {
         }
 
         if !hist_line.is_empty() {
-            let last = self.get_last_line();
-            self.text = self.text.strip_suffix(last).unwrap_or("").to_string();
-            self.text.push_str(&hist_line);
+            self.input = hist_line;
+            self.force_cursor_to_end = true;
         }
     }
 
-    fn last_line_offset(&self) -> usize {
-        // offset in buffer of start of last line
-        self.text.rfind('\n').map_or(0, |off| off + 1)
-    }
-    fn enter_search_mode(&mut self) {
-        self.save_prompt = Some(self.prompt.clone());
-        self.prompt = SEARCH_PROMPT.to_string();
+    pub(crate) fn enter_search_mode(&mut self) {
         self.search_partial = Some(String::new());
-        let last_off = self.last_line_offset();
-        self.text.truncate(last_off);
-        self.draw_prompt();
+        self.input.clear();
         self.force_cursor_to_end = true;
     }
+    // the current match is left in the input line
     fn exit_search_mode(&mut self) {
-        self.prompt = self.save_prompt.take().unwrap();
-
-        let last_off = self.last_line_offset();
-        self.text.truncate(last_off);
-        self.draw_prompt();
         self.search_partial = None;
         self.force_cursor_to_end = true;
     }
-    fn draw_prompt(&mut self) {
-        if !self.text.is_empty() && !self.text.ends_with('\n') {
-            self.text.push('\n');
-        }
-        self.text.push_str(&self.prompt);
-    }
 
     fn handle_kb(&mut self, ctx: &egui::Context) -> ConsoleEvent {
         // process all the key events in the queue
         // if they are meaningful to the console then use them and consume them
         // otherwise pass along to the textedit widget
 
-        // current cursor position
-
-        let cursor = if let Some(state) = egui::TextEdit::load_state(ctx, self.id) {
-            state.cursor.char_range().unwrap().primary.index
-        } else {
-            0
-        };
-
         // a list of keys to consume
 
         let mut kill_list = vec![];
-        let mut command = None;
+        let mut eat_text = false;
+        let mut console_event = None;
+        // where the cursor is, as a byte offset
+        self.cursor = TextEdit::load_state(ctx, self.id)
+            .and_then(|state| state.cursor.char_range())
+            .and_then(|range| {
+                self.input
+                    .char_indices()
+                    .map(|(i, _)| i)
+                    .chain(std::iter::once(self.input.len()))
+                    .nth(range.primary.index)
+            })
+            .unwrap_or(self.input.len());
+        // what was typed last frame
+        self.undo_record(false);
         ctx.input(|input| {
             for event in &input.events {
-                if let Event::Key {
-                    key,
-                    physical_key: _,
-                    pressed,
-                    modifiers,
-                    repeat: _,
-                } = event
-                {
-                    if *pressed {
-                        let (kill, msg) = self.handle_key(key, *modifiers, cursor);
+                match event {
+                    Event::Key {
+                        key,
+                        pressed: true,
+                        modifiers,
+                        ..
+                    } => {
+                        let (kill, msg) = self.handle_key(key, *modifiers);
+                        self.undo_record(true);
                         if kill {
                             kill_list.push((*modifiers, *key));
+                            // alt chords can type a character too (on a mac alt-f is ƒ)
+                            eat_text |= modifiers.alt;
                         }
-                        command = msg;
+                        console_event = msg;
                         // if the user pressed enter we are done
-                        if command.is_some() {
+                        if console_event.is_some() {
                             break;
                         }
                     }
+                    Event::Text(text) | Event::Paste(text) if self.search_partial.is_some() => {
+                        self.search_type(text);
+                        eat_text = true;
+                    }
+                    // in vi normal mode typing is commands
+                    Event::Text(text) if self.vi_normal() => {
+                        self.vi_type(text);
+                        self.undo_record(true);
+                        eat_text = true;
+                    }
+                    Event::Paste(_) if self.vi_normal() => eat_text = true,
+                    _ => {}
                 }
             }
         });
@@ -557,13 +1102,18 @@ impl ConsoleWindow {
         for (modifiers, key) in kill_list {
             Self::consume_key(ctx, modifiers, key);
         }
-
-        if let Some(command) = command {
-            return ConsoleEvent::Command(command);
+        // and keep typed text away from the input line while searching, or after an alt chord
+        if eat_text {
+            ctx.input_mut(|inp| {
+                inp.events
+                    .retain(|e| !matches!(e, Event::Text(_) | Event::Paste(_)))
+            });
         }
-        ConsoleEvent::None
+
+        console_event.unwrap_or(ConsoleEvent::None)
     }
 }
+
 /// A builder for the console window
 ///
 pub struct ConsoleBuilder {
@@ -571,6 +1121,22 @@ pub struct ConsoleBuilder {
     history_size: usize,
     scrollback_size: usize,
     tab_quote_character: char,
+    theme: ConsoleTheme,
+    completer: Option<Box<dyn Completer + Send>>,
+    match_mode: MatchMode,
+    completion_style: CompletionStyle,
+    autosuggest: bool,
+    keymap: Keymap,
+    edit_mode: EditMode,
+    continuation_prompt: String,
+    validator: Slot<dyn Fn(&str) -> bool + Send>,
+    history_store: Slot<dyn HistoryStore + Send>,
+    search_only_successful: bool,
+    history_expansion: bool,
+    history_ignore_empty: bool,
+    history_ignore_space: bool,
+    history_duplicates: HistoryDuplicates,
+    history_ignore: Slot<dyn Fn(&str) -> bool + Send>,
 }
 
 impl Default for ConsoleBuilder {
@@ -590,6 +1156,22 @@ impl ConsoleBuilder {
             history_size: 100,
             scrollback_size: 1000,
             tab_quote_character: '\'',
+            theme: ConsoleTheme::default(),
+            completer: None,
+            match_mode: MatchMode::default(),
+            completion_style: CompletionStyle::default(),
+            autosuggest: true,
+            keymap: Keymap::default(),
+            edit_mode: EditMode::default(),
+            continuation_prompt: ".. ".to_string(),
+            validator: Slot::default(),
+            history_store: Slot::default(),
+            search_only_successful: false,
+            history_expansion: false,
+            history_ignore_empty: false,
+            history_ignore_space: false,
+            history_duplicates: HistoryDuplicates::default(),
+            history_ignore: Slot::default(),
         }
     }
     /// Set the prompt for the console
@@ -603,9 +1185,35 @@ impl ConsoleBuilder {
         self.prompt = prompt.to_string();
         self
     }
+    /// Set the prompt shown in front of the second and later lines of multi-line input, the default is `.. `
+    /// # Arguments
+    /// * `prompt` - the continuation prompt string
+    ///
+    /// # Returns
+    /// * `ConsoleBuilder` - the console builder
+    ///
+    pub fn continuation_prompt(mut self, prompt: &str) -> Self {
+        self.continuation_prompt = prompt.to_string();
+        self
+    }
+    /// Set the check for whether the input is a whole command
+    /// # Arguments
+    /// * `validator` - returns true if the input is complete
+    ///
+    /// # Returns
+    /// * `ConsoleBuilder` - the console builder
+    ///
+    /// When the validator says the input is not complete, Enter starts a new line instead of
+    /// submitting it. Shift-Enter always starts a new line. [`crate::balanced`] keeps editing
+    /// while brackets or quotes are open.
+    ///
+    pub fn validator(mut self, validator: impl Fn(&str) -> bool + Send + 'static) -> Self {
+        self.validator = Slot(Some(Box::new(validator)));
+        self
+    }
     /// Set the history size for the console
     /// # Arguments
-    /// * `size` - the size of the history
+    /// * `size` - the size of the history, 0 keeps no history
     ///
     /// # Returns
     /// * `ConsoleBuilder` - the console builder
@@ -614,6 +1222,108 @@ impl ConsoleBuilder {
         self.history_size = size;
         self
     }
+    /// Save the history in a file
+    /// # Arguments
+    /// * `path` - the history file
+    ///
+    /// # Returns
+    /// * `ConsoleBuilder` - the console builder
+    ///
+    /// The history is loaded from the file when the console is built and each command is added
+    /// to it as it is run. See [`FileHistory`].
+    ///
+    pub fn history_file(self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.history_store(FileHistory::new(path))
+    }
+    /// Save the history in a [`HistoryStore`]
+    /// # Arguments
+    /// * `store` - the store
+    ///
+    /// # Returns
+    /// * `ConsoleBuilder` - the console builder
+    ///
+    pub fn history_store(mut self, store: impl HistoryStore + Send + 'static) -> Self {
+        self.history_store = Slot(Some(Box::new(store)));
+        self
+    }
+    /// Keep empty lines out of the history, they are kept by default
+    /// # Arguments
+    /// * `ignore` - skip lines that are empty or only whitespace
+    ///
+    /// # Returns
+    /// * `ConsoleBuilder` - the console builder
+    ///
+    pub fn history_ignore_empty(mut self, ignore: bool) -> Self {
+        self.history_ignore_empty = ignore;
+        self
+    }
+    /// Keep commands that start with a space out of the history, like bash's `ignorespace`
+    /// # Arguments
+    /// * `ignore` - skip commands starting with a space
+    ///
+    /// # Returns
+    /// * `ConsoleBuilder` - the console builder
+    ///
+    pub fn history_ignore_space(mut self, ignore: bool) -> Self {
+        self.history_ignore_space = ignore;
+        self
+    }
+    /// Set what happens to a command that is already in the history,
+    /// the default is [`HistoryDuplicates::Keep`]
+    /// # Arguments
+    /// * `duplicates` - the [`HistoryDuplicates`] policy
+    ///
+    /// # Returns
+    /// * `ConsoleBuilder` - the console builder
+    ///
+    pub fn history_duplicates(mut self, duplicates: HistoryDuplicates) -> Self {
+        self.history_duplicates = duplicates;
+        self
+    }
+    /// Keep some commands out of the history, for example ones with passwords or tokens in them
+    /// # Arguments
+    /// * `ignore` - returns true for a command that should not be saved
+    ///
+    /// # Returns
+    /// * `ConsoleBuilder` - the console builder
+    ///
+    /// Ignored commands still run, they are just not remembered.
+    ///
+    pub fn history_ignore(mut self, ignore: impl Fn(&str) -> bool + Send + 'static) -> Self {
+        self.history_ignore = Slot(Some(Box::new(ignore)));
+        self
+    }
+    /// Turn on bash style history expansion, it is off by default
+    /// # Arguments
+    /// * `expand` - expand history references
+    ///
+    /// # Returns
+    /// * `ConsoleBuilder` - the console builder
+    ///
+    /// Before a command is returned `!!` is replaced by the last command, `!n` by history entry `n`
+    /// (as listed by the `history` command, from 0), `!-n` by the nth last command, `!prefix` by the
+    /// last command starting with `prefix` and `!$` by the last word of the last command. `^old^new`
+    /// reruns the last command with `old` replaced by `new`. The expanded command is shown under the
+    /// one typed. An event that is not in the history is an error and nothing is run.
+    /// Use `\!` or single quotes for a plain `!`.
+    ///
+    pub fn history_expansion(mut self, expand: bool) -> Self {
+        self.history_expansion = expand;
+        self
+    }
+    /// Make ctrl-r history search skip the commands that failed
+    /// # Arguments
+    /// * `only` - skip failed commands
+    ///
+    /// # Returns
+    /// * `ConsoleBuilder` - the console builder
+    ///
+    /// A command has failed if the app said so with [`ConsoleWindow::command_status`].
+    ///
+    pub fn search_only_successful(mut self, only: bool) -> Self {
+        self.search_only_successful = only;
+        self
+    }
     /// Set the scrollback size for the console
     /// # Arguments
     /// * `size` - the size of the scrollback
@@ -638,6 +1348,97 @@ impl ConsoleBuilder {
         self.tab_quote_character = quote;
         self
     }
+    /// Set the styles used for errors, warnings and info messages
+    /// # Arguments
+    /// * `theme` - the theme
+    ///
+    /// # Returns
+    /// * `ConsoleBuilder` - the console builder
+    ///
+    pub fn theme(mut self, theme: ConsoleTheme) -> Self {
+        self.theme = theme;
+        self
+    }
+    /// Set what tab completes
+    /// # Arguments
+    /// * `completer` - the [`Completer`], it must be `Send` so the console can be
+    ///   moved to another thread
+    ///
+    /// # Returns
+    /// * `ConsoleBuilder` - the console builder
+    ///
+    /// The default completes the first word from the command table and the rest as paths,
+    /// which is `CommandCompleter.chain(FsCompleter::new())`.
+    ///
+    pub fn completer(mut self, completer: impl Completer + Send + 'static) -> Self {
+        self.completer = Some(Box::new(completer));
+        self
+    }
+    /// Set how tab completion matches what has been typed, the default is [`MatchMode::Prefix`]
+    /// # Arguments
+    /// * `mode` - the [`MatchMode`]
+    ///
+    /// # Returns
+    /// * `ConsoleBuilder` - the console builder
+    ///
+    /// With [`MatchMode::Fuzzy`] the best matches are listed first. The matched characters
+    /// are highlighted in the completion popup.
+    ///
+    pub fn match_mode(mut self, mode: MatchMode) -> Self {
+        self.match_mode = mode;
+        self
+    }
+    /// Set what tab does when there is more than one candidate, the default is [`CompletionStyle::Popup`]
+    /// # Arguments
+    /// * `style` - the [`CompletionStyle`]
+    ///
+    /// # Returns
+    /// * `ConsoleBuilder` - the console builder
+    ///
+    pub fn completion_style(mut self, style: CompletionStyle) -> Self {
+        self.completion_style = style;
+        self
+    }
+    /// Turn the history suggestions on or off, they are on by default
+    /// # Arguments
+    /// * `autosuggest` - show suggestions
+    ///
+    /// # Returns
+    /// * `ConsoleBuilder` - the console builder
+    ///
+    /// As you type, the latest history line that starts with what you have typed is shown
+    /// greyed out after the cursor. Right arrow or End take it, Alt-Right takes the next word of it.
+    ///
+    pub fn autosuggest(mut self, autosuggest: bool) -> Self {
+        self.autosuggest = autosuggest;
+        self
+    }
+    /// Set which keys do what, the default is [`Keymap::default`]
+    /// # Arguments
+    /// * `keymap` - the [`Keymap`]
+    ///
+    /// # Returns
+    /// * `ConsoleBuilder` - the console builder
+    ///
+    pub fn keymap(mut self, keymap: Keymap) -> Self {
+        self.keymap = keymap;
+        self
+    }
+    /// Set how the input line is edited, the default is [`EditMode::Emacs`]
+    /// # Arguments
+    /// * `mode` - the [`EditMode`]
+    ///
+    /// # Returns
+    /// * `ConsoleBuilder` - the console builder
+    ///
+    /// In vi mode the prompt starts with `(ins)` or `(cmd)`. Normal mode has the motions `h l w b e 0 $`,
+    /// the operators `d c y` (doubled for the whole line), `x`, `p`, `P`, `i a I A D C`, `u` to undo and `/` or `?`
+    /// to search the history. The keymap still applies in both modes.
+    ///
+    pub fn edit_mode(mut self, mode: EditMode) -> Self {
+        self.edit_mode = mode;
+        self
+    }
     /// Build the console window
     /// # Returns
     /// * `ConsoleWindow` - the console window
@@ -648,6 +1449,124 @@ impl ConsoleBuilder {
         cons.history_size = self.history_size;
         cons.scrollback_size = self.scrollback_size;
         cons.tab_quote = self.tab_quote_character;
+        cons.theme = self.theme;
+        cons.match_mode = self.match_mode;
+        cons.completion_style = self.completion_style;
+        cons.autosuggest = self.autosuggest;
+        cons.keymap = self.keymap;
+        cons.edit_mode = self.edit_mode;
+        cons.continuation_prompt = self.continuation_prompt;
+        cons.validator = self.validator;
+        cons.history_store = self.history_store;
+        cons.search_only_successful = self.search_only_successful;
+        cons.history_expansion = self.history_expansion;
+        cons.history_ignore_empty = self.history_ignore_empty;
+        cons.history_ignore_space = self.history_ignore_space;
+        cons.history_duplicates = self.history_duplicates;
+        cons.history_ignore = self.history_ignore;
+        cons.history_load();
+        if let Some(completer) = self.completer {
+            cons.completer = Slot(Some(completer));
+        }
         cons
     }
 }
+#[test]
+fn test_styled_lines() {
+    use crate::style::SpanStyle;
+    let mut console = ConsoleWindow::new(">> ");
+    console.scrollback_size = 3;
+    console.write("one");
+    console.write_error("two");
+    console.write_styled(&[Span::new("th"), Span::new("ree\nfour").bold()]);
+    let lines: Vec<_> = console.scrollback.range(0..3).collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0].text, "two");
+    assert_eq!(lines[0].styles, vec![(0..3, console.theme.error)]);
+    assert_eq!(lines[1].text, "three");
+    assert_eq!(lines[1].styles, vec![(2..5, SpanStyle::default().bold())]);
+    assert_eq!(lines[2].text, "four");
+    assert_eq!(lines[2].styles, vec![(0..4, SpanStyle::default().bold())]);
+}
+#[test]
+fn test_autosuggest() {
+    let mut console = ConsoleBuilder::new().build();
+    for command in ["git status", "cargo build", "git stash pop", "git log"] {
+        console
+            .command_history
+            .push_back(HistoryEntry::new(command));
+    }
+    let ctx = Context::default();
+    let frame = |console: &mut ConsoleWindow, events: Vec<Event>| {
+        let input = egui::RawInput {
+            events,
+            ..Default::default()
+        };
+        let _ = ctx.run(input, |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                console.draw(ui);
+            });
+            ctx.memory_mut(|mem| mem.request_focus(console.id));
+        });
+    };
+    let key = |key, modifiers| Event::Key {
+        key,
+        physical_key: None,
+        pressed: true,
+        repeat: false,
+        modifiers,
+    };
+    frame(&mut console, Vec::new());
+    frame(&mut console, vec![Event::Text("git st".to_string())]);
+    // the latest history line starting with the input
+    assert_eq!(console.suggestion.as_deref(), Some("ash pop"));
+    // alt-right takes a word of it
+    frame(&mut console, vec![key(Key::ArrowRight, Modifiers::ALT)]);
+    assert_eq!(console.input, "git stash");
+    assert_eq!(console.suggestion.as_deref(), Some(" pop"));
+    // end takes the rest
+    frame(&mut console, vec![key(Key::End, Modifiers::NONE)]);
+    assert_eq!(console.input, "git stash pop");
+    assert_eq!(console.suggestion, None);
+
+    // nothing is suggested with the cursor inside the line, and the keys move it as usual
+    console.input = "git".to_string();
+    frame(&mut console, vec![key(Key::ArrowLeft, Modifiers::NONE)]);
+    frame(&mut console, Vec::new());
+    assert_eq!((console.cursor, console.suggestion.as_deref()), (2, None));
+    for end in [Key::ArrowRight, Key::End] {
+        frame(&mut console, vec![key(end, Modifiers::NONE)]);
+        frame(&mut console, Vec::new());
+        assert_eq!((console.input.as_str(), console.cursor), ("git", 3));
+        assert_eq!(console.suggestion.as_deref(), Some(" log"));
+        frame(&mut console, vec![key(Key::ArrowLeft, Modifiers::NONE)]);
+    }
+}
+#[test]
+fn test_console_send() {
+    // apps move the console into other threads or a static Mutex
+    fn send<T: Send>() {}
+    send::<ConsoleWindow>();
+}
+#[test]
+fn test_writer() {
+    use std::io::Write as _;
+    fn send_sync<T: Send + Sync>() {}
+    send_sync::<ConsoleWriter>();
+    let mut console = ConsoleWindow::new(">> ");
+    let mut writer = console.writer();
+    let other = writer.clone();
+    std::thread::spawn(move || other.write_error("from a thread"))
+        .join()
+        .unwrap();
+    write!(writer, "partial ").unwrap();
+    writeln!(writer, "line").unwrap();
+    write!(writer, "left over").unwrap();
+    drop(writer);
+    console.drain_writers();
+    let lines: Vec<_> = console.scrollback.range(0..3).collect();
+    assert_eq!(lines[0].text, "from a thread");
+    assert_eq!(lines[0].styles, vec![(0..13, console.theme.error)]);
+    assert_eq!(lines[1].text, "partial line");
+    assert_eq!(lines[2].text, "left over");
+}
@@ -0,0 +1,315 @@
+use std::ops::Range;
+
+use crate::ConsoleWindow;
+
+// how many kills are remembered for yank-pop
+const KILL_RING_SIZE: usize = 16;
+// how many undo steps are kept
+const UNDO_LIMIT: usize = 100;
+
+// readline style editing of the input line, the cursor is `self.cursor` (a byte offset)
+impl ConsoleWindow {
+    fn edit_cursor(&self) -> usize {
+        let mut cursor = self.cursor.min(self.input.len());
+        while !self.input.is_char_boundary(cursor) {
+            cursor -= 1;
+        }
+        cursor
+    }
+    pub(crate) fn move_to(&mut self, pos: usize) {
+        self.cursor = pos;
+        self.move_cursor_to = Some(pos);
+        self.force_cursor_to_end = false;
+    }
+    // cut `range` out of the input into the kill ring
+    pub(crate) fn kill(&mut self, range: Range<usize>) {
+        if range.is_empty() {
+            return;
+        }
+        let killed: String = self.input.drain(range.clone()).collect();
+        self.kill_ring_push(killed);
+        self.move_to(range.start);
+    }
+    // remember text for yanking, dropping the oldest when the ring is full
+    pub(crate) fn kill_ring_push(&mut self, text: String) {
+        if self.kill_ring.len() >= KILL_RING_SIZE {
+            self.kill_ring.remove(0);
+        }
+        self.kill_ring.push(text);
+        self.yanked = None;
+    }
+    pub(crate) fn kill_to_end(&mut self) {
+        self.kill(self.edit_cursor()..self.input.len());
+    }
+    pub(crate) fn kill_to_start(&mut self) {
+        self.kill(0..self.edit_cursor());
+    }
+    // back to the previous whitespace, like ctrl-w in a terminal
+    pub(crate) fn kill_word_back(&mut self) {
+        let cursor = self.edit_cursor();
+        self.kill(word_start(&self.input, cursor, char::is_whitespace)..cursor);
+    }
+    // paste the last kill
+    pub(crate) fn yank(&mut self) {
+        let Some(text) = self.kill_ring.last().cloned() else {
+            return;
+        };
+        let cursor = self.edit_cursor();
+        self.input.insert_str(cursor, &text);
+        self.yanked = Some((cursor..cursor + text.len(), self.kill_ring.len() - 1));
+        self.move_to(cursor + text.len());
+    }
+    // straight after a yank, swap what was yanked for the kill before it
+    pub(crate) fn yank_pop(&mut self) {
+        let Some((range, index)) = self.yanked.take() else {
+            return;
+        };
+        let yanked = self.kill_ring.get(index).map(String::as_str);
+        if self.input.get(range.clone()) != yanked || self.edit_cursor() != range.end {
+            return;
+        }
+        let index = (index + self.kill_ring.len() - 1) % self.kill_ring.len();
+        let text = self.kill_ring[index].clone();
+        self.input.replace_range(range.clone(), &text);
+        self.yanked = Some((range.start..range.start + text.len(), index));
+        self.move_to(range.start + text.len());
+    }
+    pub(crate) fn word_back(&mut self) {
+        let pos = word_start(&self.input, self.edit_cursor(), |c| !c.is_alphanumeric());
+        self.move_to(pos);
+    }
+    pub(crate) fn word_forward(&mut self) {
+        let pos = word_end(&self.input, self.edit_cursor(), |c| !c.is_alphanumeric());
+        self.move_to(pos);
+    }
+    // a line break at the cursor, for multi-line input
+    pub(crate) fn insert_newline(&mut self) {
+        let cursor = self.edit_cursor();
+        self.input.insert(cursor, '\n');
+        self.move_to(cursor + 1);
+    }
+    // delete the character under the cursor
+    pub(crate) fn delete_char(&mut self) {
+        let cursor = self.edit_cursor();
+        if let Some(c) = self.input[cursor..].chars().next() {
+            self.input.replace_range(cursor..cursor + c.len_utf8(), "");
+            self.move_to(cursor);
+        }
+    }
+    // note a change to the line since the last call as an undo step. Typing a word carries on
+    // the step before it, unless `action` says the change was a console action (a kill, a completion...)
+    pub(crate) fn undo_record(&mut self, action: bool) {
+        let (line, cursor) = &self.undo_current;
+        if *line == self.input {
+            return;
+        }
+        let typed = !action && typed_word(line, *cursor, &self.input);
+        if !(typed && self.undo_typing) {
+            if self.undo.len() >= UNDO_LIMIT {
+                self.undo.remove(0);
+            }
+            self.undo.push(self.undo_current.clone());
+        }
+        self.redo.clear();
+        self.undo_typing = typed;
+        self.undo_current = (self.input.clone(), self.cursor.min(self.input.len()));
+    }
+    pub(crate) fn undo(&mut self) {
+        self.undo_record(true);
+        if let Some(previous) = self.undo.pop() {
+            let current = std::mem::replace(&mut self.undo_current, previous);
+            self.redo.push(current);
+            self.undo_restore();
+        }
+    }
+    pub(crate) fn redo(&mut self) {
+        self.undo_record(true);
+        if let Some(next) = self.redo.pop() {
+            let current = std::mem::replace(&mut self.undo_current, next);
+            self.undo.push(current);
+            self.undo_restore();
+        }
+    }
+    fn undo_restore(&mut self) {
+        let (line, cursor) = self.undo_current.clone();
+        self.input = line;
+        self.undo_typing = false;
+        self.move_to(cursor);
+    }
+    // a new line, nothing to undo
+    pub(crate) fn undo_reset(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.undo_typing = false;
+        self.undo_current = (self.input.clone(), self.input.len());
+    }
+    // swap the characters either side of the cursor and move past them,
+    // at the end of the line swap the last two
+    pub(crate) fn transpose(&mut self) {
+        let mut cursor = self.edit_cursor();
+        if cursor == self.input.len() {
+            match self.input.chars().next_back() {
+                Some(c) => cursor -= c.len_utf8(),
+                None => return,
+            }
+        }
+        let (Some(before), Some(after)) = (
+            self.input[..cursor].chars().next_back(),
+            self.input[cursor..].chars().next(),
+        ) else {
+            return;
+        };
+        let start = cursor - before.len_utf8();
+        let end = cursor + after.len_utf8();
+        self.input
+            .replace_range(start..end, &format!("{}{}", after, before));
+        self.move_to(end);
+    }
+}
+
+/// Are the brackets and quotes in `text` closed, a validator for [`crate::ConsoleBuilder::validator`]
+///
+/// `()`, `[]` and `{}` must balance and `'`, `"` and `` ` `` quotes be closed. Brackets inside quotes
+/// do not count and a backslash escapes the next character, so a trailing backslash continues the line.
+/// A closing bracket too many counts as complete, the app gets to report the error.
+pub fn balanced(text: &str) -> bool {
+    let mut open = 0usize;
+    let mut quote = None;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (_, '\\') => {
+                let escaped = chars.next();
+                if escaped.is_none() {
+                    return false;
+                }
+            }
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'' | '`') => quote = Some(c),
+            (None, '(' | '[' | '{') => open += 1,
+            (None, ')' | ']' | '}') => match open.checked_sub(1) {
+                Some(still_open) => open = still_open,
+                None => return true,
+            },
+            _ => {}
+        }
+    }
+    open == 0 && quote.is_none()
+}
+
+// is `new` `old` with some non whitespace typed at `cursor`
+fn typed_word(old: &str, cursor: usize, new: &str) -> bool {
+    let Some(inserted) = new.len().checked_sub(old.len()) else {
+        return false;
+    };
+    let (before, after) = old.split_at(cursor.min(old.len()));
+    inserted > 0
+        && new.starts_with(before)
+        && new.ends_with(after)
+        && new
+            .get(before.len()..before.len() + inserted)
+            .is_some_and(|typed| !typed.contains(char::is_whitespace))
+}
+
+// where the word before `pos` starts, words are runs of chars that are not `gap`
+fn word_start(text: &str, pos: usize, gap: impl Fn(char) -> bool) -> usize {
+    text[..pos]
+        .trim_end_matches(&gap)
+        .trim_end_matches(|c| !gap(c))
+        .len()
+}
+
+// where the word after `pos` ends
+fn word_end(text: &str, pos: usize, gap: impl Fn(char) -> bool) -> usize {
+    let rest = text[pos..]
+        .trim_start_matches(&gap)
+        .trim_start_matches(|c| !gap(c));
+    text.len() - rest.len()
+}
+
+#[test]
+fn test_line_editing() {
+    let mut console = crate::ConsoleBuilder::new().build();
+    console.input = "git commit -m 'fix it'".to_string();
+    console.cursor = console.input.len();
+    console.word_back();
+    assert_eq!(console.cursor, 19);
+    console.word_back();
+    console.word_back();
+    assert_eq!(console.cursor, 12);
+    console.word_forward();
+    assert_eq!(console.cursor, 13);
+    console.kill_to_end();
+    assert_eq!(console.input, "git commit -m");
+    console.kill_word_back();
+    assert_eq!(console.input, "git commit ");
+    console.kill_to_start();
+    assert_eq!(console.input, "");
+    console.yank();
+    assert_eq!(console.input, "git commit ");
+    console.yank_pop();
+    assert_eq!(console.input, "-m");
+    console.yank_pop();
+    assert_eq!(console.input, " 'fix it'");
+    console.yank_pop();
+    assert_eq!(console.input, "git commit ");
+
+    console.input = "teh".to_string();
+    console.cursor = 3;
+    console.transpose();
+    assert_eq!(console.input, "the");
+    console.cursor = 1;
+    console.transpose();
+    assert_eq!((console.input.as_str(), console.cursor), ("hte", 2));
+    console.cursor = 0;
+    console.delete_char();
+    assert_eq!(console.input, "te");
+}
+
+#[test]
+fn test_undo() {
+    let mut console = crate::ConsoleBuilder::new().build();
+    // typing, a frame at a time
+    for line in ["g", "gi", "git", "git ", "git s", "git st"] {
+        console.input = line.to_string();
+        console.cursor = line.len();
+        console.undo_record(false);
+    }
+    console.kill_word_back();
+    console.undo_record(true);
+    assert_eq!(console.input, "git ");
+    console.undo();
+    assert_eq!(console.input, "git st");
+    console.undo();
+    assert_eq!(console.input, "git ");
+    console.undo();
+    assert_eq!(console.input, "git");
+    console.undo();
+    console.undo();
+    assert_eq!(console.input, "");
+    console.redo();
+    console.redo();
+    assert_eq!((console.input.as_str(), console.cursor), ("git ", 4));
+    // a change drops the redo steps
+    console.input = "git x".to_string();
+    console.undo_record(false);
+    console.redo();
+    assert_eq!(console.input, "git x");
+    console.undo_reset();
+    console.undo();
+    assert_eq!(console.input, "git x");
+}
+
+#[test]
+fn test_balanced() {
+    assert!(balanced(""));
+    assert!(balanced("print(\"hi\")"));
+    assert!(!balanced("fn main() {"));
+    assert!(!balanced("{\"a\": [1, 2"));
+    assert!(balanced("{\"a\": [1, 2]}"));
+    assert!(!balanced("echo 'one"));
+    assert!(balanced("echo ')' \\("));
+    assert!(!balanced("ls \\"));
+    assert!(balanced("oops)"));
+}
@@ -0,0 +1,130 @@
+use std::collections::VecDeque;
+
+use crate::{history::HistoryEntry, ConsoleWindow};
+
+// bash style history expansion, `!!`, `!n`, `!-n`, `!prefix`, `!$` and `^old^new^`.
+// Returns None if the line has nothing to expand, or the error for an event that is not in the history.
+// `n` is the index shown by the history command, the oldest entry is 0
+pub(crate) fn expand_history(
+    line: &str,
+    history: &VecDeque<HistoryEntry>,
+) -> Result<Option<String>, String> {
+    let last = || history.back().map(|entry| entry.command.as_str());
+
+    // quick substitution, the last command with the first `old` replaced by `new`
+    if let Some(rest) = line.strip_prefix('^') {
+        let mut parts = rest.splitn(3, '^');
+        let old = parts.next().unwrap_or_default();
+        let new = parts.next().unwrap_or_default();
+        let tail = parts.next().unwrap_or_default();
+        return match last() {
+            Some(previous) if !old.is_empty() && previous.contains(old) => {
+                Ok(Some(previous.replacen(old, new, 1) + tail))
+            }
+            _ => Err(format!("^{}^{}: substitution failed", old, new)),
+        };
+    }
+
+    let mut expanded = String::with_capacity(line.len());
+    let mut changed = false;
+    let mut quoted = false;
+    let mut rest = line;
+    while let Some(c) = rest.chars().next() {
+        rest = &rest[c.len_utf8()..];
+        match c {
+            // nothing happens inside single quotes
+            '\'' => quoted = !quoted,
+            '\\' if rest.starts_with('!') && !quoted => {
+                expanded.push('!');
+                rest = &rest[1..];
+                changed = true;
+                continue;
+            }
+            '!' if !quoted => {
+                if let Some((event, len)) = event(rest, history)? {
+                    expanded.push_str(&event);
+                    rest = &rest[len..];
+                    changed = true;
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        expanded.push(c);
+    }
+    Ok(changed.then_some(expanded))
+}
+
+// what the event after a `!` expands to and how long it is, None if it is not an event
+fn event(text: &str, history: &VecDeque<HistoryEntry>) -> Result<Option<(String, usize)>, String> {
+    let len = match text.chars().next() {
+        // a ! on its own is just a !
+        None => return Ok(None),
+        Some(c) if c.is_whitespace() || c == '=' || c == '(' => return Ok(None),
+        Some('!' | '$') => 1,
+        Some(_) => text.find(char::is_whitespace).unwrap_or(text.len()),
+    };
+    let name = &text[..len];
+    let from_end = |back: usize| history.len().checked_sub(back).and_then(|i| history.get(i));
+    let entry = match name {
+        "!" | "$" => from_end(1),
+        _ => match name.strip_prefix('-').unwrap_or(name).parse::<usize>() {
+            Ok(back) if name.starts_with('-') => from_end(back),
+            Ok(index) => history.get(index),
+            Err(_) => history
+                .iter()
+                .rev()
+                .find(|entry| entry.command.starts_with(name)),
+        },
+    };
+    let Some(entry) = entry else {
+        return Err(format!("!{}: event not found", name));
+    };
+    if name == "$" {
+        let word = ConsoleWindow::digest_line(entry.command.trim())
+            .into_iter()
+            .rfind(|word| !word.is_empty())
+            .unwrap_or_default();
+        return Ok(Some((word.to_string(), len)));
+    }
+    Ok(Some((entry.command.clone(), len)))
+}
+
+#[test]
+fn test_history_expansion() {
+    let history: VecDeque<HistoryEntry> = ["git status", "cat 'my file.txt'", "ls -l src"]
+        .into_iter()
+        .map(HistoryEntry::new)
+        .collect();
+    let expand = |line: &str| expand_history(line, &history);
+    assert_eq!(expand("ls"), Ok(None));
+    assert_eq!(expand("sudo !!"), Ok(Some("sudo ls -l src".to_string())));
+    assert_eq!(expand("!0"), Ok(Some("git status".to_string())));
+    assert_eq!(
+        expand("!-2 | wc"),
+        Ok(Some("cat 'my file.txt' | wc".to_string()))
+    );
+    assert_eq!(expand("!gi"), Ok(Some("git status".to_string())));
+    assert_eq!(expand("vi !$"), Ok(Some("vi src".to_string())));
+    assert_eq!(expand("echo hi!"), Ok(None));
+    assert_eq!(
+        expand("echo '!!' \\!!"),
+        Ok(Some("echo '!!' !!".to_string()))
+    );
+    assert_eq!(expand("!3"), Err("!3: event not found".to_string()));
+    assert_eq!(expand("!nope"), Err("!nope: event not found".to_string()));
+
+    assert_eq!(expand("^src^docs"), Ok(Some("ls -l docs".to_string())));
+    assert_eq!(
+        expand("^-l^-a^ | less"),
+        Ok(Some("ls -a src | less".to_string()))
+    );
+    assert_eq!(
+        expand("^xyz^abc"),
+        Err("^xyz^abc: substitution failed".to_string())
+    );
+    assert_eq!(
+        expand_history("!!", &VecDeque::new()),
+        Err("!!: event not found".to_string())
+    );
+}
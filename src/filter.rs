@@ -0,0 +1,33 @@
+// per target levels, shared by the log and tracing bridges
+
+// the level for `target`, the longest matching target prefix wins,
+// "a::b" matches "a::b" and "a::b::c" but not "a::bc"
+pub(crate) fn level_for<L: Copy>(targets: &[(String, L)], default: L, target: &str) -> L {
+    targets
+        .iter()
+        .filter(|(prefix, _)| {
+            target
+                .strip_prefix(prefix.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+        })
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map_or(default, |(_, level)| *level)
+}
+
+// set the level for a target, replacing the one it had
+pub(crate) fn set_level<L>(targets: &mut Vec<(String, L)>, target: &str, level: L) {
+    match targets.iter_mut().find(|(t, _)| t == target) {
+        Some((_, l)) => *l = level,
+        None => targets.push((target.to_string(), level)),
+    }
+}
+
+#[test]
+fn test_level_for() {
+    let mut targets = vec![("app".to_string(), 1), ("app::net".to_string(), 2)];
+    set_level(&mut targets, "app", 3);
+    assert_eq!(level_for(&targets, 0, "app"), 3);
+    assert_eq!(level_for(&targets, 0, "app::net::tcp"), 2);
+    assert_eq!(level_for(&targets, 0, "app::network"), 3);
+    assert_eq!(level_for(&targets, 0, "apple"), 0);
+}
@@ -0,0 +1,686 @@
+use std::{
+    collections::HashSet,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use web_time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::ConsoleWindow;
+
+/// A command in the history, with what is known about how it went
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+pub struct HistoryEntry {
+    /// The command line
+    pub command: String,
+    /// When it was run, in seconds since the unix epoch
+    pub timestamp: Option<u64>,
+    /// How long it took, from being submitted until the app prompted again
+    pub duration: Option<Duration>,
+    /// Whether it worked, if the app said, see [`ConsoleWindow::command_status`]
+    pub success: Option<bool>,
+    /// The history tag when it was run, see [`ConsoleWindow::set_history_tag`]
+    pub tag: Option<String>,
+}
+
+impl HistoryEntry {
+    /// An entry with just the command
+    pub fn new(command: impl Into<String>) -> Self {
+        Self {
+            command: command.into(),
+            ..Self::default()
+        }
+    }
+    // one line of a history file, the command and then tab separated fields
+    fn encode(&self) -> String {
+        let mut line = escape(&self.command);
+        if let Some(timestamp) = self.timestamp {
+            line.push_str(&format!("\ttime={}", timestamp));
+        }
+        if let Some(duration) = self.duration {
+            line.push_str(&format!("\tduration={}", duration.as_millis()));
+        }
+        if let Some(success) = self.success {
+            line.push_str(if success {
+                "\tstatus=ok"
+            } else {
+                "\tstatus=failed"
+            });
+        }
+        if let Some(tag) = &self.tag {
+            line.push_str(&format!("\ttag={}", escape(tag)));
+        }
+        line
+    }
+    // fields that do not make sense are left out, not errors
+    fn decode(line: &str) -> Self {
+        let mut fields = line.split('\t');
+        let mut entry = Self::new(unescape(fields.next().unwrap_or_default()));
+        for (name, value) in fields.filter_map(|field| field.split_once('=')) {
+            match name {
+                "time" => entry.timestamp = value.parse().ok(),
+                "duration" => entry.duration = value.parse().ok().map(Duration::from_millis),
+                "status" => entry.success = Some(value == "ok"),
+                "tag" => entry.tag = Some(unescape(value)),
+                _ => {}
+            }
+        }
+        entry
+    }
+}
+
+// the saved history, state saved by versions before HistoryEntry has plain command strings
+#[cfg(feature = "persistence")]
+pub(crate) fn deserialize_entries<'de, D>(
+    deserializer: D,
+) -> Result<std::collections::VecDeque<HistoryEntry>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum Saved {
+        Entry(HistoryEntry),
+        Command(String),
+    }
+    let saved: Vec<Saved> = serde::Deserialize::deserialize(deserializer)?;
+    Ok(saved
+        .into_iter()
+        .map(|saved| match saved {
+            Saved::Entry(entry) => entry,
+            Saved::Command(command) => HistoryEntry::new(command),
+        })
+        .collect())
+}
+
+/// What happens to a command that is already in the history, see [`crate::ConsoleBuilder::history_duplicates`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+pub enum HistoryDuplicates {
+    /// Keep every command
+    #[default]
+    Keep,
+    /// Skip a command that is the same as the one before it
+    IgnoreConsecutive,
+    /// Remove the older copies, so each command is in the history once
+    EraseOlder,
+}
+
+impl HistoryDuplicates {
+    // apply to a history, oldest first
+    fn dedup(self, entries: &mut Vec<HistoryEntry>) {
+        match self {
+            Self::Keep => {}
+            Self::IgnoreConsecutive => {
+                entries.dedup_by(|newer, older| newer.command == older.command)
+            }
+            Self::EraseOlder => {
+                let mut seen = HashSet::new();
+                let mut newest = Vec::with_capacity(entries.len());
+                for entry in entries.drain(..).rev() {
+                    if seen.insert(entry.command.clone()) {
+                        newest.push(entry);
+                    }
+                }
+                newest.reverse();
+                *entries = newest;
+            }
+        }
+    }
+}
+
+/// Where the command history is saved, see [`crate::ConsoleBuilder::history_store`]
+///
+/// The console loads the history when it is built, appends each command when it finishes and
+/// trims the store when the history is over [`crate::ConsoleBuilder::history_size`]. Errors
+/// are shown in the console.
+///
+/// [`FileHistory`] keeps it in a file, [`MemoryHistory`] in memory.
+pub trait HistoryStore {
+    /// The saved history, oldest first
+    fn load(&mut self) -> io::Result<Vec<HistoryEntry>>;
+    /// Save a command that has just finished
+    fn append(&mut self, entry: &HistoryEntry) -> io::Result<()>;
+    /// Drop all but the newest `keep` entries
+    fn trim(&mut self, keep: usize) -> io::Result<()>;
+    /// Drop the whole history
+    fn clear(&mut self) -> io::Result<()> {
+        self.trim(0)
+    }
+}
+
+/// History kept in a plain text file, one command per line
+///
+/// Commands are appended as they finish, so several copies of an app can share a history file;
+/// their commands are merged in the order they finished. A lock file next to the history file
+/// (the same name with `.lock` added) keeps them from writing at the same time. It is only there
+/// while one of them reads or writes. One that has not been touched for ten seconds was left behind
+/// by an app that died and is taken over, otherwise an app gives up waiting after a second.
+///
+/// Trimming rewrites the file atomically, through a temporary file that replaces it. So that not
+/// every command rewrites the file it may grow a quarter over the size it is trimmed to.
+///
+/// Each line is a command followed by tab separated fields, `time=` (unix seconds), `duration=`
+/// (milliseconds), `status=ok` or `status=failed` and `tag=`. Backslashes, newlines and tabs
+/// are saved as `\\`, `\n` and `\t`, so multi-line commands stay whole.
+#[derive(Debug, Clone)]
+pub struct FileHistory {
+    path: PathBuf,
+    // how many entries the file has, as far as we know
+    len: usize,
+}
+
+impl FileHistory {
+    /// History in the file `path`, it is created when the first command is saved
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            len: 0,
+        }
+    }
+    /// The history file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    // the path with something added to the file name
+    fn sibling(&self, suffix: &str) -> PathBuf {
+        let mut name = self.path.file_name().unwrap_or_default().to_os_string();
+        name.push(suffix);
+        self.path.with_file_name(name)
+    }
+    // hold the returned lock to keep other instances out
+    fn lock(&self) -> io::Result<Lock> {
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let lock = Lock::new(self.sibling(".lock"));
+        let started = Instant::now();
+        loop {
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&lock.path)
+            {
+                Ok(mut file) => {
+                    file.write_all(lock.token.as_bytes())?;
+                    return Ok(lock);
+                }
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                    if lock.is_stale() {
+                        // nobody holds a lock this long, it was left behind
+                        match fs::remove_file(&lock.path) {
+                            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                            _ => continue,
+                        }
+                    }
+                    if started.elapsed() > LOCK_TIMEOUT {
+                        return Err(io::Error::new(
+                            io::ErrorKind::TimedOut,
+                            format!("{} is locked by another instance", self.path.display()),
+                        ));
+                    }
+                    thread::sleep(Duration::from_millis(10));
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+    fn read(&self) -> io::Result<Vec<HistoryEntry>> {
+        match fs::read_to_string(&self.path) {
+            Ok(text) => Ok(text.lines().map(HistoryEntry::decode).collect()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+// how long to wait for another instance to finish with the history file
+const LOCK_TIMEOUT: Duration = Duration::from_secs(1);
+// a lock file this old was left behind by an instance that died
+const LOCK_STALE: Duration = Duration::from_secs(10);
+
+// the lock file, holding a token that is unique to this lock. It is removed when dropped,
+// unless it has been taken over and holds someone else's token
+struct Lock {
+    path: PathBuf,
+    token: String,
+}
+
+impl Lock {
+    fn new(path: PathBuf) -> Self {
+        static COUNT: AtomicU64 = AtomicU64::new(0);
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_nanos());
+        let token = format!(
+            "{} {} {}",
+            std::process::id(),
+            nanos,
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        );
+        Self { path, token }
+    }
+    // has the lock file not been touched for longer than anyone holds it
+    fn is_stale(&self) -> bool {
+        fs::metadata(&self.path)
+            .and_then(|meta| meta.modified())
+            .is_ok_and(|modified| modified.elapsed().is_ok_and(|age| age > LOCK_STALE))
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        if fs::read_to_string(&self.path).is_ok_and(|token| token == self.token) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+impl HistoryStore for FileHistory {
+    fn load(&mut self) -> io::Result<Vec<HistoryEntry>> {
+        let _lock = self.lock()?;
+        let entries = self.read()?;
+        self.len = entries.len();
+        Ok(entries)
+    }
+    fn append(&mut self, entry: &HistoryEntry) -> io::Result<()> {
+        let _lock = self.lock()?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(format!("{}\n", entry.encode()).as_bytes())?;
+        self.len += 1;
+        Ok(())
+    }
+    fn trim(&mut self, keep: usize) -> io::Result<()> {
+        if self.len <= keep + keep / 4 {
+            return Ok(());
+        }
+        let _lock = self.lock()?;
+        // read it again, other instances may have added to it
+        let mut entries = self.read()?;
+        entries.drain(..entries.len().saturating_sub(keep));
+        let temp = self.sibling(".tmp");
+        let mut file = File::create(&temp)?;
+        for entry in &entries {
+            writeln!(file, "{}", entry.encode())?;
+        }
+        file.sync_all()?;
+        fs::rename(&temp, &self.path)?;
+        self.len = entries.len();
+        Ok(())
+    }
+    fn clear(&mut self) -> io::Result<()> {
+        self.len = usize::MAX;
+        self.trim(0)
+    }
+}
+
+/// History kept in memory, for tests
+///
+/// Clones share the same history, so keep a clone to look at what the console saved.
+/// ```ignore
+/// let history = MemoryHistory::new();
+/// let console = ConsoleBuilder::new().history_store(history.clone()).build();
+/// ...
+/// assert_eq!(history.entries()[0].command, "help");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MemoryHistory {
+    entries: Arc<Mutex<Vec<HistoryEntry>>>,
+}
+
+impl MemoryHistory {
+    /// An empty history
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// A history that starts with the commands `commands`, oldest first
+    pub fn with_commands<S: Into<String>>(commands: impl IntoIterator<Item = S>) -> Self {
+        let history = Self::new();
+        history
+            .entries
+            .lock()
+            .unwrap()
+            .extend(commands.into_iter().map(HistoryEntry::new));
+        history
+    }
+    /// What has been saved, oldest first
+    pub fn entries(&self) -> Vec<HistoryEntry> {
+        self.entries.lock().unwrap().clone()
+    }
+}
+
+impl HistoryStore for MemoryHistory {
+    fn load(&mut self) -> io::Result<Vec<HistoryEntry>> {
+        Ok(self.entries())
+    }
+    fn append(&mut self, entry: &HistoryEntry) -> io::Result<()> {
+        self.entries.lock().unwrap().push(entry.clone());
+        Ok(())
+    }
+    fn trim(&mut self, keep: usize) -> io::Result<()> {
+        let mut entries = self.entries.lock().unwrap();
+        let len = entries.len();
+        entries.drain(..len.saturating_sub(keep));
+        Ok(())
+    }
+}
+
+// one entry per line, with tabs between the fields
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t")
+}
+
+fn unescape(line: &str) -> String {
+    let mut entry = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => entry.push('\n'),
+            ('\\', Some('t')) => entry.push('\t'),
+            ('\\', Some('\\')) => entry.push('\\'),
+            _ => {
+                entry.push(c);
+                continue;
+            }
+        }
+        chars.next();
+    }
+    entry
+}
+
+// keeping the history and the history store up to date
+impl ConsoleWindow {
+    // should the command be kept out of the history
+    fn history_ignores(&self, command: &str) -> bool {
+        (self.history_ignore_empty && command.trim().is_empty())
+            || (self.history_ignore_space && command.starts_with(' '))
+            || self
+                .history_ignore
+                .0
+                .as_ref()
+                .is_some_and(|ignore| ignore(command))
+    }
+    // a command has been submitted, it is saved when it finishes
+    pub(crate) fn history_start(&mut self, command: &str) {
+        self.running = None;
+        if self.history_size == 0 || self.history_ignores(command) {
+            return;
+        }
+        match self.history_duplicates {
+            HistoryDuplicates::Keep => {}
+            HistoryDuplicates::IgnoreConsecutive => {
+                if self
+                    .command_history
+                    .back()
+                    .is_some_and(|last| last.command == command)
+                {
+                    return;
+                }
+            }
+            HistoryDuplicates::EraseOlder => {
+                self.command_history.retain(|old| old.command != command)
+            }
+        }
+        if self.command_history.len() >= self.history_size {
+            self.command_history.pop_front();
+        }
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since| since.as_secs())
+            .ok();
+        self.command_history.push_back(HistoryEntry {
+            timestamp,
+            tag: self.history_tag.clone(),
+            ..HistoryEntry::new(command)
+        });
+        self.running = Some(Instant::now());
+    }
+    // the app has prompted again, so the last command is done
+    pub(crate) fn history_finish(&mut self) {
+        let Some(started) = self.running.take() else {
+            return;
+        };
+        if let Some(entry) = self.command_history.back_mut() {
+            entry.duration = Some(started.elapsed());
+            let entry = entry.clone();
+            self.history_append(&entry);
+        }
+    }
+    // replace the history with what the store has
+    pub(crate) fn history_load(&mut self) {
+        let Some(store) = &mut self.history_store.0 else {
+            return;
+        };
+        match store.load() {
+            Ok(mut entries) => {
+                // the store may have duplicates the console would not have kept
+                self.history_duplicates.dedup(&mut entries);
+                entries.drain(..entries.len().saturating_sub(self.history_size));
+                self.command_history = entries.into();
+                self.history_cursor = None;
+            }
+            Err(err) => self.write_error(&format!("history: {}", err)),
+        }
+    }
+    fn history_append(&mut self, entry: &HistoryEntry) {
+        let Some(store) = &mut self.history_store.0 else {
+            return;
+        };
+        if let Err(err) = store
+            .append(entry)
+            .and_then(|_| store.trim(self.history_size))
+        {
+            self.write_error(&format!("history: {}", err));
+        }
+    }
+    pub(crate) fn history_clear(&mut self) {
+        let Some(store) = &mut self.history_store.0 else {
+            return;
+        };
+        if let Err(err) = store.clear() {
+            self.write_error(&format!("history: {}", err));
+        }
+    }
+}
+
+#[test]
+fn test_history_escape() {
+    for entry in [
+        "plain",
+        "two\nlines",
+        "back\\slash",
+        "\\n",
+        "end\\",
+        "\\\\n\n",
+        "a\tb",
+    ] {
+        assert_eq!(unescape(&escape(entry)), entry);
+        assert!(!escape(entry).contains(['\n', '\t']));
+    }
+    assert_eq!(unescape("odd \\x"), "odd \\x");
+
+    let entry = HistoryEntry {
+        command: "cd\t'my files'".to_string(),
+        timestamp: Some(1_700_000_000),
+        duration: Some(Duration::from_millis(1500)),
+        success: Some(false),
+        tag: Some("/home/me".to_string()),
+    };
+    assert_eq!(
+        entry.encode(),
+        "cd\\t'my files'\ttime=1700000000\tduration=1500\tstatus=failed\ttag=/home/me"
+    );
+    assert_eq!(HistoryEntry::decode(&entry.encode()), entry);
+    assert_eq!(HistoryEntry::decode("ls"), HistoryEntry::new("ls"));
+    let odd = HistoryEntry::decode("ls\ttime=soon\tcolour=red\tstatus=ok");
+    assert_eq!((odd.timestamp, odd.success), (None, Some(true)));
+}
+
+#[test]
+fn test_file_history() {
+    let dir = std::env::temp_dir().join(format!("egui_console_history_{}", std::process::id()));
+    let path = dir.join("history");
+    let mut one = FileHistory::new(&path);
+    let mut two = FileHistory::new(&path);
+    let commands = |entries: Vec<HistoryEntry>| -> Vec<String> {
+        entries.into_iter().map(|entry| entry.command).collect()
+    };
+    assert!(one.load().unwrap().is_empty());
+    one.append(&HistoryEntry::new("ls")).unwrap();
+    two.append(&HistoryEntry::new("cat\nmore")).unwrap();
+    one.append(&HistoryEntry::new("pwd")).unwrap();
+    assert_eq!(
+        commands(two.load().unwrap()),
+        vec!["ls", "cat\nmore", "pwd"]
+    );
+    // within the slack nothing is rewritten
+    two.trim(3).unwrap();
+    for n in 0..3 {
+        one.append(&HistoryEntry::new(n.to_string())).unwrap();
+    }
+    one.trim(4).unwrap();
+    assert_eq!(one.load().unwrap().len(), 6);
+    one.append(&HistoryEntry::new("3")).unwrap();
+    one.trim(4).unwrap();
+    assert_eq!(commands(two.load().unwrap()), vec!["0", "1", "2", "3"]);
+    two.clear().unwrap();
+    assert!(one.load().unwrap().is_empty());
+    // a lock that is held is waited for, and left alone
+    let lock = one.sibling(".lock");
+    fs::write(&lock, "someone else").unwrap();
+    let err = one.append(&HistoryEntry::new("ls")).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    assert_eq!(fs::read_to_string(&lock).unwrap(), "someone else");
+    // one left behind is taken over
+    let old = SystemTime::now() - LOCK_STALE * 2;
+    File::options()
+        .write(true)
+        .open(&lock)
+        .unwrap()
+        .set_modified(old)
+        .unwrap();
+    one.append(&HistoryEntry::new("ls")).unwrap();
+    assert_eq!(commands(two.load().unwrap()), vec!["ls"]);
+    assert!(!lock.exists());
+    // a lock that was taken over is not removed by the one it was taken from
+    let held = one.lock().unwrap();
+    fs::write(&lock, "someone else").unwrap();
+    drop(held);
+    assert!(lock.exists());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_history_store() {
+    let history = MemoryHistory::with_commands(["one", "two", "three"]);
+    let mut console = crate::ConsoleBuilder::new()
+        .history_size(2)
+        .history_store(history.clone())
+        .build();
+    assert_eq!(console.get_history(), vec!["two", "three"]);
+    console.prompting = true;
+    console.set_history_tag(Some("project".to_string()));
+    console.input = "four".to_string();
+    console.run_action(crate::Action::Submit);
+    // saved when it is finished
+    assert_eq!(history.entries().len(), 3);
+    console.command_status(false);
+    console.prompt();
+    let entries = history.entries();
+    assert_eq!(entries.len(), 2);
+    let last = &entries[1];
+    assert_eq!(last.command, "four");
+    assert_eq!(
+        (last.success, last.tag.as_deref()),
+        (Some(false), Some("project"))
+    );
+    assert!(last.timestamp.is_some() && last.duration.is_some());
+    assert_eq!(console.history_entries().back(), Some(last));
+    console.clear_history();
+    assert!(history.entries().is_empty());
+
+    // a size of 0 keeps no history at all
+    let mut console = crate::ConsoleBuilder::new()
+        .history_size(0)
+        .history_store(history.clone())
+        .build();
+    console.prompting = true;
+    console.input = "five".to_string();
+    console.run_action(crate::Action::Submit);
+    console.prompt();
+    assert!(console.get_history().is_empty());
+    assert!(history.entries().is_empty());
+}
+
+#[test]
+fn test_history_policies() {
+    let run = |console: &mut ConsoleWindow, commands: &[&str]| {
+        for command in commands {
+            console.prompting = true;
+            console.input = command.to_string();
+            console.run_action(crate::Action::Submit);
+            console.prompt();
+        }
+        console.get_history()
+    };
+    // by default everything is kept
+    let mut console = crate::ConsoleBuilder::new().build();
+    assert_eq!(run(&mut console, &["ls", "ls", ""]), vec!["ls", "ls", ""]);
+
+    let mut console = crate::ConsoleBuilder::new()
+        .history_duplicates(HistoryDuplicates::IgnoreConsecutive)
+        .history_ignore_empty(true)
+        .build();
+    assert_eq!(
+        run(&mut console, &["ls", "", "  ", "ls", "pwd", "ls"]),
+        vec!["ls", "pwd", "ls"]
+    );
+
+    let history = MemoryHistory::with_commands(["ls", "pwd", "ls", "cd"]);
+    let mut console = crate::ConsoleBuilder::new()
+        .history_duplicates(HistoryDuplicates::EraseOlder)
+        .history_ignore_space(true)
+        .history_ignore(|command| command.contains("token="))
+        .history_store(history.clone())
+        .build();
+    assert_eq!(console.get_history(), vec!["pwd", "ls", "cd"]);
+    let commands = [" secret", "login token=abc", "pwd", "ls"];
+    assert_eq!(run(&mut console, &commands), vec!["cd", "pwd", "ls"]);
+    assert_eq!(history.entries().len(), 6);
+}
+
+#[cfg(feature = "persistence")]
+#[test]
+fn test_load_saved_history() {
+    // saved before history entries had more than the command, and before most of the settings
+    let saved = r#"(history_size:50,scrollback_size:1000,command_history:["ls","cd src"],prompt:"$ ",prompt_len:2,id:(12345),save_prompt:None,tab_quote:'"',tab_command_table:["ls","cd"])"#;
+    let loaded: ConsoleWindow = ron::from_str(saved).unwrap();
+    assert_eq!(loaded.get_history(), vec!["ls", "cd src"]);
+    assert_eq!(loaded.history_entries()[0], HistoryEntry::new("ls"));
+    assert_eq!(loaded.history_size, 50);
+    assert_eq!(loaded.prompt, "$ ");
+    assert_eq!(loaded.history_duplicates, HistoryDuplicates::Keep);
+    assert_eq!(loaded.edit_mode, crate::EditMode::Emacs);
+
+    let mut console = crate::ConsoleBuilder::new().build();
+    console.command_history.push_back(HistoryEntry {
+        success: Some(true),
+        tag: Some("src".to_string()),
+        ..HistoryEntry::new("ls")
+    });
+    let loaded: ConsoleWindow = ron::from_str(&ron::to_string(&console).unwrap()).unwrap();
+    assert_eq!(loaded.history_entries(), console.history_entries());
+}
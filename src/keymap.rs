@@ -0,0 +1,270 @@
+use std::{collections::BTreeMap, fmt};
+
+use egui::{Key, KeyboardShortcut, Modifiers};
+use serde_derive::{Deserialize, Serialize};
+
+/// Something the console does when a key is pressed, see [`Keymap`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Action {
+    /// The previous history line
+    HistoryPrev,
+    /// The next history line
+    HistoryNext,
+    /// Start a reverse history search, or find the next match
+    ReverseSearch,
+    /// Leave history search or close the completion popup
+    CancelSearch,
+    /// Tab completion
+    Complete,
+    /// Tab completion, starting from the last candidate
+    CompleteBack,
+    /// Run the line, or start another if the validator says it is not complete
+    Submit,
+    /// Start a new line of multi-line input
+    Newline,
+    /// Clear the console
+    ClearScreen,
+    /// Move to the start of the line
+    LineStart,
+    /// Move to the end of the line, taking the history suggestion if there is one
+    LineEnd,
+    /// Move back a word
+    WordBack,
+    /// Move forward a word
+    WordForward,
+    /// Kill from the cursor to the end of the line
+    KillToEnd,
+    /// Kill from the start of the line to the cursor
+    KillToStart,
+    /// Kill back to the previous whitespace
+    KillWordBack,
+    /// Insert the last kill
+    Yank,
+    /// Straight after a yank, swap the yanked text for the kill before it
+    YankPop,
+    /// Swap the characters either side of the cursor
+    Transpose,
+    /// Undo the last change to the line
+    Undo,
+    /// Redo what was undone
+    Redo,
+    /// Delete the character under the cursor, on an empty line send [`crate::ConsoleEvent::Eof`]
+    DeleteOrEof,
+    /// Take the history suggestion, the key does its usual job if there is none
+    AcceptSuggestion,
+    /// Take the next word of the history suggestion, the key does its usual job if there is none
+    AcceptSuggestionWord,
+    /// Nothing, the key goes to the input line. For taking a key away from a default action
+    Unbound,
+}
+
+/// Which keys do which [`Action`]s
+///
+/// [`Keymap::default`] has the usual bindings, arrows for history, Tab to complete, Ctrl-R to search
+/// and the readline editing keys. Bind more with [`Keymap::bind`] and hand it to [`crate::ConsoleBuilder::keymap`].
+///
+/// ```ignore
+/// let keymap = Keymap::default()
+///     .bind(KeyboardShortcut::new(Modifiers::COMMAND, Key::K), Action::ClearScreen)
+///     .bind(KeyboardShortcut::new(Modifiers::CTRL, Key::T), Action::Unbound);
+/// ```
+///
+/// [`Modifiers::CTRL`] is the control key everywhere, [`Modifiers::COMMAND`] is cmd on a mac and control elsewhere.
+///
+/// A keymap serializes as a map from shortcut to action, so it can be loaded from a file
+/// with any serde format. The bindings in the file are added to the defaults.
+/// ```json
+/// { "Cmd+K": "ClearScreen", "Ctrl+P": "HistoryPrev", "Ctrl+T": "Unbound" }
+/// ```
+/// Shortcuts are modifiers, `Ctrl`, `Cmd`, `Alt` and `Shift`, and an egui key name joined with `+`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keymap {
+    bindings: Vec<(KeyboardShortcut, Action)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let ctrl = Modifiers::CTRL;
+        let alt = Modifiers::ALT;
+        let none = Modifiers::NONE;
+        let bindings = [
+            (none, Key::ArrowUp, Action::HistoryPrev),
+            (none, Key::ArrowDown, Action::HistoryNext),
+            (ctrl, Key::R, Action::ReverseSearch),
+            (none, Key::Escape, Action::CancelSearch),
+            (none, Key::Tab, Action::Complete),
+            (Modifiers::SHIFT, Key::Tab, Action::CompleteBack),
+            (none, Key::Enter, Action::Submit),
+            (Modifiers::SHIFT, Key::Enter, Action::Newline),
+            (ctrl, Key::L, Action::ClearScreen),
+            (ctrl, Key::A, Action::LineStart),
+            (ctrl, Key::E, Action::LineEnd),
+            (alt, Key::B, Action::WordBack),
+            (alt, Key::F, Action::WordForward),
+            (ctrl, Key::K, Action::KillToEnd),
+            (ctrl, Key::U, Action::KillToStart),
+            (ctrl, Key::W, Action::KillWordBack),
+            (ctrl, Key::Y, Action::Yank),
+            (alt, Key::Y, Action::YankPop),
+            (ctrl, Key::T, Action::Transpose),
+            (ctrl, Key::D, Action::DeleteOrEof),
+            (Modifiers::COMMAND, Key::Z, Action::Undo),
+            (Modifiers::COMMAND | Modifiers::SHIFT, Key::Z, Action::Redo),
+            // ctrl-_ in emacs
+            (ctrl | Modifiers::SHIFT, Key::Minus, Action::Undo),
+            (none, Key::ArrowRight, Action::AcceptSuggestion),
+            (none, Key::End, Action::AcceptSuggestion),
+            (alt, Key::ArrowRight, Action::AcceptSuggestionWord),
+        ];
+        Self {
+            bindings: bindings
+                .into_iter()
+                .map(|(modifiers, key, action)| (KeyboardShortcut::new(modifiers, key), action))
+                .collect(),
+        }
+    }
+}
+
+impl Keymap {
+    /// A keymap with nothing bound
+    pub fn empty() -> Self {
+        Self {
+            bindings: Vec::new(),
+        }
+    }
+    /// Bind a shortcut, replacing what it was bound to
+    pub fn bind(mut self, shortcut: KeyboardShortcut, action: Action) -> Self {
+        self.bindings.retain(|(s, _)| *s != shortcut);
+        self.bindings.push((shortcut, action));
+        self
+    }
+    /// The action for a key press
+    /// # Arguments
+    /// * `modifiers` - the modifiers held
+    /// * `key` - the key
+    ///
+    /// # Returns
+    /// * `Option<Action>` - None if the key is not bound (or is [`Action::Unbound`])
+    ///
+    pub fn action(&self, modifiers: Modifiers, key: Key) -> Option<Action> {
+        // later bindings win, when both Ctrl+X and Cmd+X match
+        self.bindings
+            .iter()
+            .rev()
+            .find(|(s, _)| s.logical_key == key && modifiers.matches_exact(s.modifiers))
+            .map(|(_, action)| *action)
+            .filter(|action| *action != Action::Unbound)
+    }
+    /// The shortcuts bound to an action
+    pub fn shortcuts(&self, action: Action) -> impl Iterator<Item = KeyboardShortcut> + '_ {
+        self.bindings
+            .iter()
+            .filter(move |(_, a)| *a == action)
+            .map(|(s, _)| *s)
+    }
+}
+
+// "Ctrl+Shift+Z", the form used in keymap files
+struct ShortcutName(KeyboardShortcut);
+
+impl fmt::Display for ShortcutName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let modifiers = self.0.modifiers;
+        let names = [
+            (modifiers.ctrl, "Ctrl"),
+            (modifiers.command, "Cmd"),
+            (modifiers.mac_cmd && !modifiers.command, "MacCmd"),
+            (modifiers.alt, "Alt"),
+            (modifiers.shift, "Shift"),
+        ];
+        for (_, name) in names.iter().filter(|(held, _)| *held) {
+            write!(f, "{}+", name)?;
+        }
+        f.write_str(self.0.logical_key.name())
+    }
+}
+
+fn parse_shortcut(text: &str) -> Result<KeyboardShortcut, String> {
+    let mut parts: Vec<&str> = text.split('+').map(str::trim).collect();
+    // "Ctrl++" is ctrl and plus
+    if text.ends_with("++") {
+        parts.truncate(parts.len() - 2);
+        parts.push("+");
+    }
+    let (key, modifiers) = parts.split_last().ok_or("empty shortcut")?;
+    let key = Key::from_name(key)
+        .or_else(|| Key::from_name(&key.to_uppercase()))
+        .ok_or_else(|| format!("unknown key '{}' in '{}'", key, text))?;
+    let mut held = Modifiers::NONE;
+    for modifier in modifiers {
+        held = held
+            | match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => Modifiers::CTRL,
+                "cmd" | "command" => Modifiers::COMMAND,
+                "maccmd" => Modifiers::MAC_CMD,
+                "alt" | "option" => Modifiers::ALT,
+                "shift" => Modifiers::SHIFT,
+                _ => return Err(format!("unknown modifier '{}' in '{}'", modifier, text)),
+            };
+    }
+    Ok(KeyboardShortcut::new(held, key))
+}
+
+impl serde::Serialize for Keymap {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(
+            self.bindings
+                .iter()
+                .map(|(shortcut, action)| (ShortcutName(*shortcut).to_string(), action)),
+        )
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Keymap {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bindings = BTreeMap::<String, Action>::deserialize(deserializer)?;
+        bindings
+            .into_iter()
+            .try_fold(Keymap::default(), |keymap, (shortcut, action)| {
+                Ok(keymap.bind(parse_shortcut(&shortcut)?, action))
+            })
+            .map_err(serde::de::Error::custom::<String>)
+    }
+}
+
+#[test]
+fn test_keymap() {
+    use serde::de::{value::Error, value::MapDeserializer, Deserialize};
+
+    let keymap = Keymap::default();
+    let ctrl = Modifiers {
+        ctrl: true,
+        command: true,
+        ..Modifiers::NONE
+    };
+    assert_eq!(keymap.action(ctrl, Key::R), Some(Action::ReverseSearch));
+    assert_eq!(keymap.action(Modifiers::NONE, Key::R), None);
+    // the mac command key is not ctrl
+    assert_eq!(keymap.action(Modifiers::MAC_CMD, Key::R), None);
+    assert_eq!(
+        keymap.action(Modifiers::SHIFT, Key::Tab),
+        Some(Action::CompleteBack)
+    );
+
+    for text in ["Ctrl+R", "Cmd+Shift+Z", "Alt+Right", "Tab", "MacCmd+Up"] {
+        let shortcut = parse_shortcut(text).unwrap();
+        assert_eq!(ShortcutName(shortcut).to_string(), text);
+    }
+    assert_eq!(parse_shortcut("ctrl+p"), parse_shortcut("Ctrl+P"));
+    assert_eq!(parse_shortcut("Ctrl++"), parse_shortcut("Ctrl+Plus"));
+    assert!(parse_shortcut("Hyper+P").is_err());
+    assert!(parse_shortcut("Ctrl+Nope").is_err());
+
+    let file = [("Cmd+K", "ClearScreen"), ("Ctrl+T", "Unbound")];
+    let keymap = Keymap::deserialize(MapDeserializer::<_, Error>::new(file.into_iter())).unwrap();
+    assert_eq!(keymap.action(ctrl, Key::K), Some(Action::ClearScreen));
+    assert_eq!(keymap.action(ctrl, Key::T), None);
+    assert_eq!(keymap.action(ctrl, Key::A), Some(Action::LineStart));
+    let bad = [("Ctrl+K", "Explode")];
+    assert!(Keymap::deserialize(MapDeserializer::<_, Error>::new(bad.into_iter())).is_err());
+}
@@ -46,18 +46,138 @@
 ///
 ///```
 ///
+/// # Output
+///
+/// [`ConsoleWindow::write`] renders ANSI colour escape sequences, so output meant for a terminal can be reused as is.
+/// [`ConsoleWindow::write_styled`] writes a line made of [`Span`]s, and [`ConsoleWindow::write_error`], [`ConsoleWindow::write_warning`]
+/// and [`ConsoleWindow::write_info`] use the colours from the [`ConsoleTheme`] set on the builder.
+///
+/// To write from another thread (or anywhere you dont have the `ConsoleWindow`) get a [`ConsoleWriter`] from [`ConsoleWindow::writer`].
+/// It also implements `std::io::Write` and `std::fmt::Write`.
+///
+/// With the `log` feature, [`ConsoleLogger`] sends `log` records to the console. Hand its [`LogHandle`] to
+/// [`ConsoleWindow::log_command`] and the user can change the levels with the `loglevel` command.
+///
+/// With the `tracing` feature, [`ConsoleLayer`] is a `tracing_subscriber` layer that writes events, with their span context, to the console.
+/// Hand its [`TraceHandle`] to [`ConsoleWindow::trace_command`] and the user can change the filter with the `tracefilter` command.
+///
+/// # Commands
+///
+/// Rather than matching command strings yourself you can register [`Command`]s with a [`CommandRegistry`] and
+/// pass it each [`ConsoleEvent::Command`]. It splits the line into arguments, calls the handler, adds the commands to tab completion,
+/// has built in `help`, `clear` and `history` commands, and suggests the closest command when the user makes a typo.
+///
+/// With the `clap` feature, [`ClapCommands`] does the same for commands defined with a `clap::Command`, and tab completion
+/// follows the definition: subcommands, flags, possible values and paths.
+///
+/// # Tab completion
+///
+/// Tab asks a [`Completer`] for [`Candidate`]s. One candidate is inserted straight away, more open a popup above the word
+/// that is filtered as you type. Pick with the arrows, Tab and Shift-Tab, accept with Enter and dismiss with Escape. The default completes the first word from
+/// the command table and everything else as a path. Install your own with [`ConsoleBuilder::completer`], a closure will do, and
+/// combine completers with [`Completer::chain`]. [`CommandCompleter`] and [`FsCompleter`] are the two default ones.
+/// [`FsCompleter`] expands `~` and environment variables, hides dot files until you type a dot, and can be limited
+/// to directories or some extensions ([`FsFilter`]) or completed from a root other than the current directory. It can complete
+/// from something other than the real file system, an asset tree say, through [`CompletionFs`]. [`MemoryFs`] is one held in memory.
+///
+/// [`ConsoleBuilder::match_mode`] picks how candidates are matched: by prefix (the default), ignoring case, by substring or
+/// fuzzily, with the best matches first. The matched characters are highlighted in the popup.
+/// With [`CompletionStyle::Bash`] the first Tab inserts what the candidates have in common and a second Tab opens the popup.
+/// Shift-Tab opens it at the last candidate.
+///
+/// # Editing
+///
+/// The input line has the usual readline keys: ctrl-a and ctrl-e go to the start and end of the line, alt-b and alt-f move by words,
+/// ctrl-k, ctrl-u and ctrl-w kill to the end, to the start and the word before the cursor, ctrl-y yanks the last kill back
+/// and alt-y swaps it for the kill before. Ctrl-t transposes characters, ctrl-l clears the screen and ctrl-d deletes the
+/// character under the cursor, or on an empty line returns [`ConsoleEvent::Eof`].
+///
+/// Ctrl-z undoes changes to the line, ctrl-shift-z redoes them (ctrl-_ also undoes). Typing, completions, history lines
+/// and kills and yanks are all undone; a typed word is one step. Submitting a line starts a fresh undo history.
+///
+/// [`ConsoleBuilder::edit_mode`] with [`EditMode::Vi`] gives vi insert and normal modes instead, with the mode shown in the prompt.
+/// In normal mode u undoes and ctrl-r redoes.
+///
+/// Shift-Enter starts a new line, for multi-line commands. With [`ConsoleBuilder::validator`] Enter also starts a new line
+/// while the input is not complete (try [`balanced`]), the later lines get the [`ConsoleBuilder::continuation_prompt`].
+/// [`ConsoleEvent::Command`] has all the lines, and history recalls them as one entry.
+///
+/// All the keys can be changed. Each console behaviour is an [`Action`] and a [`Keymap`] says which keys do which; pass your own
+/// to [`ConsoleBuilder::keymap`]. A keymap can be loaded from a file with serde.
 ///
 ///#  Command history
 ///
 /// - ctrl-r searches the command history
 /// - up and down arrow walk though the command history
+/// - as you type, the latest matching history line is suggested greyed out after the cursor. Right arrow or End take it,
+///   Alt-Right takes its next word. [`ConsoleBuilder::autosuggest`] turns this off
+///
+/// [`ConsoleBuilder::history_file`] saves the history in a file, adding each command as it is run. Several copies of an app
+/// can share the file. Any other [`HistoryStore`] can be used with [`ConsoleBuilder::history_store`], and [`MemoryHistory`]
+/// keeps it in memory for tests.
+///
+/// Each [`HistoryEntry`] has when the command ran, how long it took until the app prompted again, whether it worked
+/// ([`ConsoleWindow::command_status`], the command dispatchers report it) and a tag such as the working directory
+/// ([`ConsoleWindow::set_history_tag`]). [`ConsoleWindow::history_entries`] has them all, and
+/// [`ConsoleBuilder::search_only_successful`] keeps failed commands out of ctrl-r search.
+///
+/// Empty lines and a command repeating the one before are not added to the history. [`ConsoleBuilder::history_duplicates`]
+/// changes how repeats are handled, [`ConsoleBuilder::history_ignore_space`] leaves out commands typed with a leading space and
+/// [`ConsoleBuilder::history_ignore`] leaves out whatever the app says, such as commands with passwords in them.
+///
+/// [`ConsoleBuilder::history_expansion`] turns on bash style `!!`, `!n`, `!prefix`, `!$` and `^old^new` history expansion.
 ///
-/// If you want the command history to be automatically persisted you need to enable the persistence feature. This will use the eframe storage to save the command history between sessions.
+/// Or if you want the command history to be automatically persisted you need to enable the persistence feature. This will use the eframe storage to save the command history between sessions.
 ///
 /// Alternatively you can use [`ConsoleWindow::load_history`] and [`ConsoleWindow::get_history`] to manually save and load the command history.    
 #[warn(missing_docs)]
 pub mod console;
+
+mod ansi;
+mod buffer;
+#[cfg(feature = "clap")]
+mod clap_commands;
+mod command;
+mod completion;
+mod completion_fs;
+mod edit;
+mod expand;
+#[cfg(any(feature = "log", feature = "tracing"))]
+mod filter;
+mod history;
+mod keymap;
+#[cfg(feature = "log")]
+mod logger;
+mod slot;
+mod style;
 mod tab;
+#[cfg(feature = "tracing")]
+mod trace;
+mod vi;
+mod writer;
+
+#[cfg(feature = "clap")]
+pub use crate::clap_commands::{ClapCommands, ClapCompleter};
+pub use crate::command::{Command, CommandContext, CommandRegistry, CommandResult};
+pub use crate::completion::{
+    Candidate, CandidateKind, Chain, CommandCompleter, Completer, CompletionContext,
+    CompletionStyle, FsCompleter, FsFilter, Match, MatchMode,
+};
+pub use crate::completion_fs::{CompletionFs, MemoryFs, StdFs};
 pub use crate::console::ConsoleBuilder;
 pub use crate::console::ConsoleEvent;
 pub use crate::console::ConsoleWindow;
+pub use crate::console::EditMode;
+pub use crate::edit::balanced;
+pub use crate::history::{
+    FileHistory, HistoryDuplicates, HistoryEntry, HistoryStore, MemoryHistory,
+};
+pub use crate::keymap::{Action, Keymap};
+#[cfg(feature = "log")]
+pub use crate::logger::{ConsoleLogger, LogHandle};
+pub use crate::style::ConsoleTheme;
+pub use crate::style::Span;
+pub use crate::style::SpanStyle;
+#[cfg(feature = "tracing")]
+pub use crate::trace::{ConsoleLayer, TraceHandle};
+pub use crate::writer::ConsoleWriter;
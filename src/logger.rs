@@ -0,0 +1,257 @@
+use std::sync::{Arc, RwLock};
+
+use egui::Color32;
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+use crate::{filter, writer::ConsoleWriter, Span};
+
+// egui's and eframe's own crates, off by default
+const EGUI_TARGETS: &[&str] = &[
+    "egui",
+    "epaint",
+    "eframe",
+    "egui_glow",
+    "egui_wgpu",
+    "egui_winit",
+];
+
+// default level plus per target overrides
+#[derive(Debug)]
+struct Filters {
+    level: LevelFilter,
+    targets: Vec<(String, LevelFilter)>,
+}
+
+impl Filters {
+    fn level_for(&self, target: &str) -> LevelFilter {
+        filter::level_for(&self.targets, self.level, target)
+    }
+    fn max_level(&self) -> LevelFilter {
+        self.targets
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.level, Ord::max)
+    }
+}
+
+/// A handle for changing the [`ConsoleLogger`] filter while the app runs
+///
+/// Pass it to [`crate::ConsoleWindow::log_command`] to let the user change it
+/// from the prompt with the built in `loglevel` command.
+#[derive(Debug, Clone)]
+pub struct LogHandle {
+    filters: Arc<RwLock<Filters>>,
+}
+
+impl LogHandle {
+    /// The default level
+    pub fn level(&self) -> LevelFilter {
+        self.filters.read().unwrap().level
+    }
+    /// Set the default level, used for targets without their own level
+    pub fn set_level(&self, level: LevelFilter) {
+        let mut filters = self.filters.write().unwrap();
+        filters.level = level;
+        log::set_max_level(filters.max_level());
+    }
+    /// Set the level for a target (module path) and everything below it
+    pub fn set_target_level(&self, target: &str, level: LevelFilter) {
+        let mut filters = self.filters.write().unwrap();
+        filter::set_level(&mut filters.targets, target, level);
+        log::set_max_level(filters.max_level());
+    }
+
+    // the built in loglevel command
+    // loglevel                   - show the levels
+    // loglevel <level>           - set the default level
+    // loglevel <level> <target>  - set the level for a target
+    pub(crate) fn command(&self, args: &[&str]) -> Result<String, String> {
+        match args {
+            [] => {
+                let filters = self.filters.read().unwrap();
+                let mut out = format!("default: {}", filters.level);
+                for (target, level) in &filters.targets {
+                    out.push_str(&format!("\n{}: {}", target, level));
+                }
+                Ok(out)
+            }
+            [level, rest @ ..] if rest.len() <= 1 => {
+                let level = level
+                    .parse::<LevelFilter>()
+                    .map_err(|_| format!("unknown log level '{}'", level))?;
+                match rest.first() {
+                    Some(target) => {
+                        self.set_target_level(target, level);
+                        Ok(format!("{}: {}", target, level))
+                    }
+                    None => {
+                        self.set_level(level);
+                        Ok(format!("default: {}", level))
+                    }
+                }
+            }
+            _ => Err("usage: loglevel [off|error|warn|info|debug|trace] [target]".to_string()),
+        }
+    }
+}
+
+/// A [`log::Log`] implementation that writes records to a [`crate::ConsoleWindow`]
+///
+/// Available with the `log` feature.
+///
+/// ```ignore
+/// let handle = ConsoleLogger::new(console.writer())
+///     .level(LevelFilter::Info)
+///     .target_level("my_app::net", LevelFilter::Debug)
+///     .init()?;
+/// console.log_command(handle);
+/// ```
+///
+/// Errors, warnings and info records use the console theme's colours, debug is
+/// plain and trace is grey. Records are sent through a [`ConsoleWriter`] so they can
+/// come from any thread, and anything logged before the console is first drawn is kept.
+///
+/// egui and eframe log through `log` too, their records are left out unless you give
+/// their targets a level with [`ConsoleLogger::target_level`]. Records logged on the ui thread
+/// show up the next time the console is drawn, the logger does not ask egui for a repaint
+/// there as egui may be logging while it holds its own lock.
+#[derive(Debug)]
+pub struct ConsoleLogger {
+    writer: ConsoleWriter,
+    filters: Arc<RwLock<Filters>>,
+}
+
+impl ConsoleLogger {
+    /// Create a logger writing to the console, the default level is `Info`
+    pub fn new(writer: ConsoleWriter) -> Self {
+        Self {
+            writer: writer.deferred(),
+            filters: Arc::new(RwLock::new(Filters {
+                level: LevelFilter::Info,
+                targets: EGUI_TARGETS
+                    .iter()
+                    .map(|target| (target.to_string(), LevelFilter::Off))
+                    .collect(),
+            })),
+        }
+    }
+    /// Set the default level
+    pub fn level(self, level: LevelFilter) -> Self {
+        self.filters.write().unwrap().level = level;
+        self
+    }
+    /// Set the level for a target (module path) and everything below it
+    pub fn target_level(self, target: &str, level: LevelFilter) -> Self {
+        self.handle().set_target_level(target, level);
+        self
+    }
+    /// Get a handle for changing the levels later
+    pub fn handle(&self) -> LogHandle {
+        LogHandle {
+            filters: self.filters.clone(),
+        }
+    }
+    /// Install as the global logger
+    /// # Returns
+    /// * `LogHandle` - for changing the levels later
+    ///
+    pub fn init(self) -> Result<LogHandle, SetLoggerError> {
+        let handle = self.handle();
+        let max_level = self.filters.read().unwrap().max_level();
+        log::set_boxed_logger(Box::new(self))?;
+        log::set_max_level(max_level);
+        Ok(handle)
+    }
+}
+
+impl Log for ConsoleLogger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.level() <= self.filters.read().unwrap().level_for(metadata.target())
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format!(
+            "{:<5} {}: {}",
+            record.level(),
+            record.target(),
+            record.args()
+        );
+        match record.level() {
+            Level::Error => self.writer.write_error(&line),
+            Level::Warn => self.writer.write_warning(&line),
+            Level::Info => self.writer.write_info(&line),
+            Level::Debug => self.writer.write_styled(vec![Span::new(line)]),
+            Level::Trace => self
+                .writer
+                .write_styled(vec![Span::new(line).color(Color32::GRAY)]),
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+#[test]
+fn test_log_filters() {
+    let logger = ConsoleLogger::new(crate::ConsoleBuilder::new().build().writer())
+        .level(LevelFilter::Warn)
+        .target_level("app::net", LevelFilter::Trace);
+    let filters = logger.filters.read().unwrap();
+    assert_eq!(filters.level_for("app"), LevelFilter::Warn);
+    assert_eq!(filters.level_for("app::net"), LevelFilter::Trace);
+    assert_eq!(filters.level_for("app::net::tcp"), LevelFilter::Trace);
+    assert_eq!(filters.level_for("app::network"), LevelFilter::Warn);
+    assert_eq!(filters.level_for("egui::context"), LevelFilter::Off);
+    assert_eq!(filters.max_level(), LevelFilter::Trace);
+}
+#[test]
+fn test_log_to_console() {
+    let mut console = crate::ConsoleBuilder::new().build();
+    let logger = ConsoleLogger::new(console.writer());
+    let handle = logger.handle();
+    let record = |level| {
+        Record::builder()
+            .level(level)
+            .target("app")
+            .args(format_args!("hello"))
+            .build()
+    };
+    logger.log(&record(Level::Error));
+    logger.log(&record(Level::Debug));
+    assert_eq!(handle.command(&["debug"]), Ok("default: DEBUG".to_string()));
+    logger.log(&record(Level::Debug));
+    assert!(handle.command(&["loud"]).is_err());
+    console.drain_writers();
+    assert_eq!(console.scrollback.len(), 2);
+}
+#[test]
+fn test_log_during_frame() {
+    let mut console = crate::ConsoleBuilder::new().build();
+    let logger = ConsoleLogger::new(console.writer()).level(LevelFilter::Debug);
+    let ctx = egui::Context::default();
+    for _ in 0..2 {
+        let _ = ctx.run(Default::default(), |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                console.draw(ui);
+            });
+            // egui logs while it holds its context lock, a repaint request then would deadlock
+            ctx.input(|_| {
+                logger.log(
+                    &Record::builder()
+                        .level(Level::Debug)
+                        .target("app")
+                        .args(format_args!("in a frame"))
+                        .build(),
+                )
+            });
+        });
+    }
+    let lines: Vec<_> = console
+        .scrollback
+        .range(0..console.scrollback.len())
+        .map(|l| l.text.clone())
+        .collect();
+    assert_eq!(lines, vec!["DEBUG app: in a frame"]);
+}
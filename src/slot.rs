@@ -0,0 +1,17 @@
+use std::fmt;
+
+// something the app plugs in, a completer, validator, history store or ignore test.
+// A wrapper so the console can still be Debug and Default
+pub(crate) struct Slot<T: ?Sized>(pub(crate) Option<Box<T>>);
+
+impl<T: ?Sized> Default for Slot<T> {
+    fn default() -> Self {
+        Self(None)
+    }
+}
+
+impl<T: ?Sized> fmt::Debug for Slot<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(if self.0.is_some() { "Some(..)" } else { "None" })
+    }
+}
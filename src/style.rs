@@ -0,0 +1,200 @@
+use std::ops::Range;
+
+use egui::{text::LayoutJob, Color32, Stroke, TextFormat, TextStyle, Ui};
+
+/// Visual attributes of a run of console output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SpanStyle {
+    /// Foreground colour, `None` uses the normal text colour
+    pub color: Option<Color32>,
+    /// Background colour, `None` leaves the background alone
+    pub background: Option<Color32>,
+    /// Bold text
+    pub bold: bool,
+    /// Italic text
+    pub italic: bool,
+    /// Underlined text
+    pub underline: bool,
+}
+
+impl SpanStyle {
+    /// Set the foreground colour
+    pub fn color(mut self, color: Color32) -> Self {
+        self.color = Some(color);
+        self
+    }
+    /// Set the background colour
+    pub fn background(mut self, color: Color32) -> Self {
+        self.background = Some(color);
+        self
+    }
+    /// Make the text bold
+    pub fn bold(mut self) -> Self {
+        self.bold = true;
+        self
+    }
+    /// Make the text italic
+    pub fn italic(mut self) -> Self {
+        self.italic = true;
+        self
+    }
+    /// Underline the text
+    pub fn underline(mut self) -> Self {
+        self.underline = true;
+        self
+    }
+
+    pub(crate) fn is_plain(&self) -> bool {
+        *self == Self::default()
+    }
+
+    // egui has no bold monospace face, so bold text without an explicit
+    // colour is drawn in the strong text colour instead
+    pub(crate) fn text_format(&self, ui: &Ui) -> TextFormat {
+        let color = match (self.color, self.bold) {
+            (Some(color), _) => color,
+            (None, true) => ui.visuals().strong_text_color(),
+            // the same colour the input line TextEdit uses
+            (None, false) => ui.visuals().widgets.inactive.text_color(),
+        };
+        TextFormat {
+            font_id: TextStyle::Monospace.resolve(ui.style()),
+            color,
+            background: self.background.unwrap_or(Color32::TRANSPARENT),
+            italics: self.italic,
+            underline: if self.underline {
+                Stroke::new(1.0, color)
+            } else {
+                Stroke::NONE
+            },
+            ..Default::default()
+        }
+    }
+}
+
+/// A piece of text together with its style, see [`crate::ConsoleWindow::write_styled`]
+///
+/// ```ignore
+/// console.write_styled(&[
+///     Span::new("build "),
+///     Span::new("ok").color(Color32::GREEN).bold(),
+/// ]);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Span {
+    /// The text
+    pub text: String,
+    /// How to draw it
+    pub style: SpanStyle,
+}
+
+impl Span {
+    /// Create a plain span
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            style: SpanStyle::default(),
+        }
+    }
+    /// Replace the whole style of the span
+    pub fn style(mut self, style: SpanStyle) -> Self {
+        self.style = style;
+        self
+    }
+    /// Set the foreground colour
+    pub fn color(mut self, color: Color32) -> Self {
+        self.style = self.style.color(color);
+        self
+    }
+    /// Set the background colour
+    pub fn background(mut self, color: Color32) -> Self {
+        self.style = self.style.background(color);
+        self
+    }
+    /// Make the text bold
+    pub fn bold(mut self) -> Self {
+        self.style = self.style.bold();
+        self
+    }
+    /// Make the text italic
+    pub fn italic(mut self) -> Self {
+        self.style = self.style.italic();
+        self
+    }
+    /// Underline the text
+    pub fn underline(mut self) -> Self {
+        self.style = self.style.underline();
+        self
+    }
+}
+
+impl From<&str> for Span {
+    fn from(text: &str) -> Self {
+        Self::new(text)
+    }
+}
+
+impl From<String> for Span {
+    fn from(text: String) -> Self {
+        Self::new(text)
+    }
+}
+
+/// The styles used by [`crate::ConsoleWindow::write_error`],
+/// [`crate::ConsoleWindow::write_warning`] and [`crate::ConsoleWindow::write_info`]
+///
+/// Set with [`crate::ConsoleBuilder::theme`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConsoleTheme {
+    /// Style for errors
+    pub error: SpanStyle,
+    /// Style for warnings
+    pub warning: SpanStyle,
+    /// Style for informational messages
+    pub info: SpanStyle,
+}
+
+impl Default for ConsoleTheme {
+    fn default() -> Self {
+        // these read well on both the dark and light egui themes
+        Self {
+            error: SpanStyle::default().color(Color32::from_rgb(255, 80, 80)),
+            warning: SpanStyle::default().color(Color32::from_rgb(255, 143, 0)),
+            info: SpanStyle::default().color(Color32::from_rgb(90, 170, 255)),
+        }
+    }
+}
+
+// build the layout for `text` from the styled byte ranges that cover parts of it.
+// Everything not covered by a range is plain.
+// The galley must contain exactly `text` so ranges that have gone stale (not on
+// a char boundary or past the end) are ignored rather than trusted
+pub(crate) fn layout_job(
+    ui: &Ui,
+    text: &str,
+    styles: &[(Range<usize>, SpanStyle)],
+    wrap_width: f32,
+) -> LayoutJob {
+    let plain = SpanStyle::default().text_format(ui);
+    let mut job = LayoutJob::default();
+    job.wrap.max_width = wrap_width;
+    let mut pos = 0;
+    for (range, style) in styles {
+        if range.start < pos
+            || range.end > text.len()
+            || !text.is_char_boundary(range.start)
+            || !text.is_char_boundary(range.end)
+        {
+            continue;
+        }
+        if range.start > pos {
+            job.append(&text[pos..range.start], 0.0, plain.clone());
+        }
+        job.append(&text[range.clone()], 0.0, style.text_format(ui));
+        pos = range.end;
+    }
+    if pos < text.len() {
+        job.append(&text[pos..], 0.0, plain);
+    }
+    job
+}
@@ -1,265 +1,333 @@
-use std::path::PathBuf;
-
-use itertools::Itertools;
+use egui::{
+    text::{CCursor, LayoutJob, TextFormat},
+    text_edit::TextEditOutput,
+    Align2, Order, TextStyle, Ui,
+};
 
+use crate::completion::{CandidateKind, CompletionContext, CompletionStyle};
 use crate::ConsoleWindow;
 
 impl ConsoleWindow {
-    pub(crate) fn tab_complete(&mut self) {
-        let last = self.get_last_line().to_string();
-
-        let args = ConsoleWindow::digest_line(&last);
-        if args.is_empty() {
+    // ask the completer, a single candidate goes straight in, more than one opens the popup
+    // (bash style inserts what they have in common first and leaves the popup for the next tab).
+    // `forward` is false for shift-tab, which starts at the last candidate
+    pub(crate) fn tab_complete(&mut self, forward: bool) {
+        let cursor = self.cursor.min(self.input.len());
+        // a second tab on the same line lists what the first one found
+        if self.tab_pending && self.input == self.tab_line && cursor == self.tab_cursor {
+            self.tab_open(forward);
             return;
         }
-        let last_arg = &args[args.len() - 1];
-        let is_command_arg = args.len() == 1;
-        let mut quote_char = self.tab_quote;
-        if self.tab_string.is_empty() {
-            // means we are entering tab search mode
-
-            // the main fiddling here is for path with spaces in them on mac and windows
-            // the user can enter a partial path with quotes, which we have to strip before passing to the
-            // fs tabber and reinstate after an answer comes back
-            // else if we get a path with spaces in it from the fs tabber we have to add quotes
-
-            self.tab_quoted = false;
-
-            if last_arg.is_empty() {
-                return;
+        let ctx = CompletionContext::new(
+            &self.input,
+            cursor,
+            &self.tab_command_table,
+            self.tab_quote,
+            self.match_mode,
+        );
+        let candidates: Vec<_> = self
+            .completer
+            .0
+            .as_ref()
+            .map(|completer| completer.complete(&ctx))
+            .unwrap_or_default()
+            .into_iter()
+            // a completer can hand back any range, drop ones that dont fit the line
+            .filter(|c| c.range.end <= cursor && self.input.get(c.range.clone()).is_some())
+            .collect();
+        self.tab_line = self.input.clone();
+        self.tab_cursor = cursor;
+        self.tab_candidates = candidates;
+        match self.tab_candidates.len() {
+            0 => {}
+            1 => self.tab_insert(0),
+            _ if self.completion_style == CompletionStyle::Bash && forward => {
+                self.tab_common_prefix();
+                self.tab_pending = true;
             }
-
-            if &last_arg[0..1] == "\'" || &last_arg[0..1] == "\"" {
-                self.tab_string = last_arg[1..].to_string();
-                quote_char = last_arg.chars().next().unwrap();
+            _ => self.tab_open(forward),
+        }
+    }
+    fn tab_open(&mut self, forward: bool) {
+        self.tab_pending = false;
+        self.tab_start = self
+            .tab_candidates
+            .iter()
+            .map(|c| c.range.start)
+            .min()
+            .unwrap_or(self.tab_cursor);
+        self.tab_nth = 0;
+        self.tab_popup = true;
+        self.tab_scroll = true;
+        self.tab_filter();
+        if !forward {
+            self.tab_move(false);
+        }
+    }
+    // replace the word with the longest prefix all the candidates share, if that makes it longer.
+    // The candidates are kept, with their ranges moved to cover the new word
+    fn tab_common_prefix(&mut self) {
+        let start = self.tab_candidates[0].range.start;
+        if self.tab_candidates.iter().any(|c| c.range.start != start) {
+            return;
+        }
+        let prefix = common_prefix(self.tab_candidates.iter().map(|c| c.replacement.as_str()));
+        let word = &self.input[start..self.tab_cursor];
+        if prefix.chars().count() <= word.chars().count() {
+            return;
+        }
+        let prefix = prefix.to_string();
+        self.input.replace_range(start..self.tab_cursor, &prefix);
+        self.tab_cursor = start + prefix.len();
+        self.tab_line = self.input.clone();
+        self.move_cursor_to = Some(self.tab_cursor);
+        for candidate in &mut self.tab_candidates {
+            candidate.range = start..self.tab_cursor;
+        }
+    }
+    // replace the candidate's range, plus anything typed since tab was pressed
+    fn tab_insert(&mut self, index: usize) {
+        let candidate = self.tab_candidates[index].clone();
+        let end = candidate.range.end + self.cursor.saturating_sub(self.tab_cursor);
+        let end = end.min(self.input.len());
+        if self.input.get(candidate.range.start..end).is_none() {
+            return;
+        }
+        self.input
+            .replace_range(candidate.range.start..end, &candidate.replacement);
+        self.move_cursor_to = Some(candidate.range.start + candidate.replacement.len());
+        self.tab_close();
+    }
+    pub(crate) fn tab_accept(&mut self) {
+        if let Some(&(index, _)) = self.tab_visible.get(self.tab_nth) {
+            self.tab_insert(index);
+        }
+        self.tab_close();
+    }
+    pub(crate) fn tab_close(&mut self) {
+        self.tab_popup = false;
+        self.tab_pending = false;
+        self.tab_candidates.clear();
+        self.tab_visible.clear();
+        self.tab_nth = 0;
+    }
+    // move the popup selection, wrapping at the ends
+    pub(crate) fn tab_move(&mut self, forward: bool) {
+        let len = self.tab_visible.len();
+        if len > 0 {
+            self.tab_nth = if forward {
+                (self.tab_nth + 1) % len
             } else {
-                self.tab_string = last_arg.to_string()
+                (self.tab_nth + len - 1) % len
             };
-            self.tab_nth = 0;
-            self.tab_offset = self.text.len() - last_arg.len();
-        } else {
-            // otherwise move to the next match
-            self.tab_nth += 1;
+            self.tab_scroll = true;
         }
-        // the loop gets us back to the first match once fs tabber returns no match
-        loop {
-            if let Some(mut path) = if is_command_arg {
-                cmd_tab_complete(&self.tab_string, self.tab_nth, &self.tab_command_table)
-            } else {
-                fs_tab_complete(&self.tab_string, self.tab_nth)
-            } {
-                let mut added_quotes = false;
-
-                if path.display().to_string().contains(' ') {
-                    path = PathBuf::from(format!("{}{}{}", quote_char, path.display(), quote_char));
-                    added_quotes = true;
-                }
+    }
+    // keep only the candidates that still match what has been typed since the popup opened,
+    // best match first, remembering which characters matched for the highlight
+    fn tab_filter(&mut self) {
+        let same_start = self.input.get(..self.tab_start) == self.tab_line.get(..self.tab_start);
+        if !same_start || self.cursor < self.tab_start {
+            self.tab_close();
+            return;
+        }
+        let typed = &self.input[self.tab_start..self.cursor];
+        let typed = typed.strip_prefix(['"', '\'']).unwrap_or(typed);
+        let selected = self.tab_visible.get(self.tab_nth).map(|(i, _)| *i);
+        let mut visible: Vec<_> = self
+            .tab_candidates
+            .iter()
+            .enumerate()
+            .filter_map(|(i, c)| Some((i, self.match_mode.find_word(typed, &c.display)?)))
+            .collect();
+        // stable, so equal scores keep the completer's order
+        visible.sort_by_key(|(_, m)| -m.score);
+        self.tab_visible = visible.into_iter().map(|(i, m)| (i, m.positions)).collect();
+        if self.tab_visible.is_empty() {
+            self.tab_close();
+            return;
+        }
+        // keep the same candidate selected if it is still there
+        self.tab_nth = selected
+            .and_then(|s| self.tab_visible.iter().position(|(i, _)| *i == s))
+            .unwrap_or(0);
+    }
 
-                self.text.truncate(self.tab_offset);
-                self.force_cursor_to_end = true;
-                self.text.push_str(path.to_str().unwrap());
+    // the popup listing the candidates, above the word being completed
+    pub(crate) fn tab_popup_ui(&mut self, ui: &mut Ui, output: &TextEditOutput) {
+        if let Some(range) = output.cursor_range {
+            self.cursor = self
+                .input
+                .char_indices()
+                .map(|(i, _)| i)
+                .chain(std::iter::once(self.input.len()))
+                .nth(range.primary.ccursor.index)
+                .unwrap_or(self.input.len());
+        }
+        self.tab_filter();
+        if !self.tab_popup {
+            return;
+        }
+        let start = CCursor::new(self.input[..self.tab_start].chars().count());
+        let anchor = output
+            .galley
+            .pos_from_cursor(&output.galley.from_ccursor(start))
+            .translate(output.galley_pos.to_vec2());
 
-                self.tab_quoted = added_quotes;
-                break;
-            } else {
-                // exit if there were no matches at all
-                if self.tab_nth == 0 {
-                    break;
-                }
-                // force wrap around to first match
-                self.tab_nth = 0;
-            }
+        let font = TextStyle::Monospace.resolve(ui.style());
+        let color = ui.visuals().text_color();
+        let highlight = ui.visuals().strong_text_color();
+        let mut clicked = None;
+        egui::Area::new(self.id.with("completions"))
+            .order(Order::Foreground)
+            .pivot(Align2::LEFT_BOTTOM)
+            .fixed_pos(anchor.left_top())
+            .show(ui.ctx(), |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    egui::ScrollArea::vertical()
+                        .max_height(ui.text_style_height(&egui::TextStyle::Monospace) * 12.0)
+                        .show(ui, |ui| {
+                            egui::Grid::new(self.id.with("completion_grid"))
+                                .num_columns(2)
+                                .spacing([16.0, 0.0])
+                                .show(ui, |ui| {
+                                    for (row, (index, positions)) in
+                                        self.tab_visible.iter().enumerate()
+                                    {
+                                        let candidate = &self.tab_candidates[*index];
+                                        let icon = match candidate.kind {
+                                            CandidateKind::Command => "⚙ ",
+                                            CandidateKind::Directory => "🗀 ",
+                                            CandidateKind::File => "🗋 ",
+                                            CandidateKind::Other => "",
+                                        };
+                                        let mut label = LayoutJob::default();
+                                        let plain = TextFormat::simple(font.clone(), color);
+                                        label.append(icon, 0.0, plain.clone());
+                                        for (i, ch) in candidate.display.char_indices() {
+                                            let format = if positions.contains(&i) {
+                                                TextFormat {
+                                                    underline: egui::Stroke::new(1.0, highlight),
+                                                    ..TextFormat::simple(font.clone(), highlight)
+                                                }
+                                            } else {
+                                                plain.clone()
+                                            };
+                                            label.append(
+                                                &candidate.display[i..i + ch.len_utf8()],
+                                                0.0,
+                                                format,
+                                            );
+                                        }
+                                        let response =
+                                            ui.selectable_label(row == self.tab_nth, label);
+                                        if row == self.tab_nth && self.tab_scroll {
+                                            response.scroll_to_me(None);
+                                        }
+                                        if response.clicked() {
+                                            clicked = Some(row);
+                                        }
+                                        ui.with_layout(
+                                            egui::Layout::right_to_left(egui::Align::Center),
+                                            |ui| ui.weak(&candidate.description),
+                                        );
+                                        ui.end_row();
+                                    }
+                                });
+                        });
+                });
+            });
+        self.tab_scroll = false;
+        if let Some(row) = clicked {
+            self.tab_nth = row;
+            self.tab_accept();
+            self.undo_record(true);
+            ui.memory_mut(|mem| mem.request_focus(self.id));
         }
     }
     // chop up input line input arguments honoring quotes
 
-    fn digest_line(line: &str) -> Vec<&str> {
+    pub(crate) fn digest_line(line: &str) -> Vec<&str> {
         enum State {
-            InQuotes(char),
-            InWhite,
-            InWord,
-            NotSure,
+            Quoted(char),
+            White,
+            Word,
         }
 
-        let mut state = State::InWord;
+        let mut state = State::Word;
 
         let mut res: Vec<&str> = Vec::new();
         let mut start = 0;
 
         for (idx, ch) in line.char_indices() {
             match state {
-                State::InWord => match ch {
+                State::Word => match ch {
                     ' ' => {
                         res.push(&line[start..idx]);
-                        state = State::InWhite;
-                    }
-                    '"' | '\'' => {
-                        state = State::InQuotes(ch);
-                        start = idx;
+                        state = State::White;
                     }
+                    '"' | '\'' => state = State::Quoted(ch),
                     _ => {}
                 },
-                State::InWhite => match ch {
+                State::White => match ch {
                     ' ' => {}
                     '"' | '\'' => {
-                        state = State::InQuotes(ch);
+                        state = State::Quoted(ch);
                         start = idx;
                     }
                     _ => {
                         start = idx;
-                        state = State::InWord;
+                        state = State::Word;
                     }
                 },
-                State::InQuotes(qc) => {
+                State::Quoted(qc) => {
                     if ch == qc {
-                        res.push(&line[start..idx + 1]);
-                        state = State::NotSure;
-                        start = idx;
+                        state = State::Word;
                     }
                 }
-                State::NotSure => {
-                    if ch == ' ' {
-                        state = State::InWhite;
-                    } else {
-                        state = State::InWord;
-                    }
-                    start = idx;
-                }
             }
         }
 
         match state {
-            State::InWord => res.push(&line[start..]),
-            State::InWhite => res.push(""),
-            State::InQuotes(_) => res.push(&line[start..]),
-            State::NotSure => {}
+            State::Word => res.push(&line[start..]),
+            State::White => res.push(""),
+            State::Quoted(_) => res.push(&line[start..]),
         }
 
         res
     }
 }
-pub(crate) fn cmd_tab_complete(search: &str, nth: usize, commands: &[String]) -> Option<PathBuf> {
-    commands
-        .iter()
-        .filter(|c| c.starts_with(search))
-        .nth(nth)
-        .map(PathBuf::from)
-    //None
-}
-// return the nth matching path, or None if there isnt one
-pub(crate) fn fs_tab_complete(search: &str, nth: usize) -> Option<PathBuf> {
-    let dot_slash = if cfg!(target_os = "windows") && search.find('\\').is_some() {
-        ".\\"
-    } else {
-        "./"
+// the longest prefix of all the strings, on a char boundary
+fn common_prefix<'a>(mut strings: impl Iterator<Item = &'a str>) -> &'a str {
+    let Some(first) = strings.next() else {
+        return "";
     };
-    let search_path = PathBuf::from(search);
-
-    let mut nth = nth;
-    let mut added_dot = false;
-
-    // were we given a real path to start with?
-
-    let mut base_search = if search_path.is_dir() {
-        search_path
-    } else {
-        // no - look at the parent (ie we got "cd dir/f")
-        let parent = search_path.parent();
-        if parent.is_none() {
-            return None;
-        } else {
-            let p = parent.unwrap().to_path_buf();
-            // if empty parent then search "." (remember we added the dot so remove it later)
-            if p.display().to_string().is_empty() {
-                added_dot = true;
-                PathBuf::from(dot_slash)
-            } else if p.display().to_string() == "." {
-                // we were given . as a dir
-                PathBuf::from(dot_slash)
-            } else {
-                p
-            }
-        }
-    };
-    // convert .. to ../ or ..\
-    if base_search.display().to_string() == ".." {
-        base_search = PathBuf::from(format!(".{}", dot_slash));
-    }
-
-    let dir = std::fs::read_dir(&base_search);
-
-    if let Ok(entries) = dir {
-        // deal with platform oddities, also unwrap everything
-
-        // mac retruns things in random order - so sort
-        #[cfg(target_os = "macos")]
-        let entries = entries
-            .filter(|e| e.is_ok())
-            .map(|e| e.unwrap())
-            .sorted_by(|a, b| Ord::cmp(&a.file_name(), &b.file_name()));
-
-        // windows returns things in ascii case sensitive order
-        // this is a surprise to windows users
-        #[cfg(target_os = "windows")]
-        let entries = entries
-            .filter(|e| e.is_ok())
-            .map(|e| e.unwrap())
-            .sorted_by(|a, b| {
-                Ord::cmp(
-                    &a.file_name().to_ascii_lowercase(),
-                    &b.file_name().to_ascii_lowercase(),
-                )
-            });
-
-        // linux is well behaved!
-        #[cfg(target_os = "linux")]
-        let entries = entries.filter(|e| e.is_ok()).map(|e| e.unwrap());
-
-        for ent in entries {
-            let mut ret_path = ent.path();
-            if added_dot {
-                ret_path = ret_path.strip_prefix(dot_slash).ok()?.to_path_buf();
-            }
-            #[cfg(target_os = "windows")]
-            if ret_path
-                .display()
-                .to_string()
-                .to_ascii_lowercase()
-                .starts_with(search)
-            {
-                if nth == 0 {
-                    return Some(ret_path);
-                } else {
-                    nth -= 1;
-                }
-            }
-            #[cfg(not(target_os = "windows"))]
-            if ret_path.display().to_string().starts_with(search) {
-                if nth == 0 {
-                    return Some(ret_path);
-                } else {
-                    nth -= 1;
-                }
-            }
-        }
-    }
-    None
+    strings.fold(first, |prefix, s| {
+        let len = prefix
+            .char_indices()
+            .zip(s.chars())
+            .find(|((_, a), b)| a != b)
+            .map_or(prefix.len().min(s.len()), |((i, _), _)| i);
+        &prefix[..len]
+    })
 }
+
 #[test]
 fn test_digest_line() {
-    let mut console = ConsoleWindow::new(">> ");
-    let result = console.digest_line("cd foo");
+    let result = ConsoleWindow::digest_line("cd foo");
     assert_eq!(result, vec!["cd", "foo"]);
-    let result = console.digest_line("cd \"foo bar\"");
+    let result = ConsoleWindow::digest_line("cd \"foo bar\"");
     assert_eq!(result, vec!["cd", "\"foo bar\""]);
-    let result = console.digest_line("cd \"foo bar");
-    assert_eq!(result, vec!["cd", "\"foo", "bar"]);
-    let result = console.digest_line("cd foo bar\"");
+    let result = ConsoleWindow::digest_line("cd \"foo bar");
+    assert_eq!(result, vec!["cd", "\"foo bar"]);
+    let result = ConsoleWindow::digest_line("cd foo bar\"");
     assert_eq!(result, vec!["cd", "foo", "bar\""]);
-    let result = console.digest_line("\"cd foo bar\"");
-    assert_eq!(result, vec!["\"cd", "foo", "bar\""]);
-    let result = console.digest_line("cd\" foo bar\"");
-    assert_eq!(result, vec!["cd\"", "foo", "bar\""]);
+    let result = ConsoleWindow::digest_line("\"cd foo bar\"");
+    assert_eq!(result, vec!["\"cd foo bar\""]);
+    let result = ConsoleWindow::digest_line("cd\" foo bar\"");
+    assert_eq!(result, vec!["cd\" foo bar\""]);
+    let result = ConsoleWindow::digest_line("--name=\"a b\" x\"y z\"w");
+    assert_eq!(result, vec!["--name=\"a b\"", "x\"y z\"w"]);
 }
 #[test]
 fn test_digest_line2() {
@@ -272,10 +340,127 @@ fn test_digest_line2() {
     assert_eq!(result, vec!["cd", "\"foo bar\""]);
     let result = ConsoleWindow::digest_line("cd \"foo bar");
     assert_eq!(result, vec!["cd", "\"foo bar"]);
-    // let result = console.digest_line("cd foo bar\"");
+    // let result = ConsoleWindow::digest_line("cd foo bar\"");
     // assert_eq!(result, vec!["cd", "foo", "bar\""]);
-    // let result = console.digest_line("\"cd foo bar\"");
+    // let result = ConsoleWindow::digest_line("\"cd foo bar\"");
     // assert_eq!(result, vec!["\"cd", "foo", "bar\""]);
-    // let result = console.digest_line("cd\" foo bar\"");
+    // let result = ConsoleWindow::digest_line("cd\" foo bar\"");
     // assert_eq!(result, vec!["cd\"", "foo", "bar\""]);
 }
+#[test]
+fn test_common_prefix() {
+    assert_eq!(
+        common_prefix(["src/console.rs", "src/completion.rs"].into_iter()),
+        "src/co"
+    );
+    assert_eq!(common_prefix(["history", "hist"].into_iter()), "hist");
+    assert_eq!(common_prefix(["héllo", "hèllo"].into_iter()), "h");
+    assert_eq!(common_prefix(["abc"].into_iter()), "abc");
+    assert_eq!(common_prefix(std::iter::empty()), "");
+}
+#[cfg(test)]
+fn completing_console(style: CompletionStyle) -> ConsoleWindow {
+    let mut console = crate::ConsoleBuilder::new().completion_style(style).build();
+    *console.command_table_mut() = ["start", "stop", "status", "help"]
+        .map(String::from)
+        .to_vec();
+    console
+}
+#[cfg(test)]
+fn visible(console: &ConsoleWindow) -> Vec<&str> {
+    console
+        .tab_visible
+        .iter()
+        .map(|(i, _)| console.tab_candidates[*i].display.as_str())
+        .collect()
+}
+#[test]
+fn test_tab_popup() {
+    let mut console = completing_console(CompletionStyle::Popup);
+    console.input = "st".to_string();
+    console.cursor = 2;
+    console.run_action(crate::Action::Complete);
+    assert!(console.tab_popup);
+    assert_eq!(visible(&console), vec!["start", "stop", "status"]);
+
+    // typing filters the popup
+    console.input = "sta".to_string();
+    console.cursor = 3;
+    console.tab_filter();
+    assert_eq!(visible(&console), vec!["start", "status"]);
+
+    // the selection wraps at both ends
+    console.tab_move(true);
+    assert_eq!(console.tab_nth, 1);
+    console.tab_move(true);
+    assert_eq!(console.tab_nth, 0);
+    console.tab_move(false);
+    assert_eq!(console.tab_nth, 1);
+
+    // accepting replaces the word, including what was typed since tab
+    console.tab_accept();
+    assert_eq!(console.input, "status");
+    assert!(!console.tab_popup);
+
+    // a filter nothing matches closes the popup
+    console.input = "st".to_string();
+    console.cursor = 2;
+    console.run_action(crate::Action::Complete);
+    console.input = "stx".to_string();
+    console.cursor = 3;
+    console.tab_filter();
+    assert!(!console.tab_popup);
+}
+#[test]
+fn test_tab_popup_directory() {
+    use crate::completion::{FsCompleter, MatchMode};
+    let fs = crate::MemoryFs::new()
+        .file("src/completion.rs")
+        .file("src/console.rs")
+        .file("src/lib.rs");
+    let popup = |mode: MatchMode, line: &str| {
+        let mut console = crate::ConsoleBuilder::new()
+            .match_mode(mode)
+            .completer(FsCompleter::new().fs(fs.clone()))
+            .build();
+        console.input = line.to_string();
+        console.cursor = line.len();
+        console.run_action(crate::Action::Complete);
+        console
+    };
+    // only the file name is matched, by the completer and by the popup filter
+    let console = popup(MatchMode::Substring, "cat src/o");
+    assert!(console.tab_popup);
+    assert_eq!(
+        visible(&console),
+        vec!["src/completion.rs", "src/console.rs"]
+    );
+    let mut console = popup(MatchMode::Fuzzy, "cat src/cn");
+    assert!(console.tab_popup);
+    assert_eq!(
+        visible(&console),
+        vec!["src/console.rs", "src/completion.rs"]
+    );
+    console.input.push('o');
+    console.cursor += 1;
+    console.tab_filter();
+    assert_eq!(visible(&console), vec!["src/console.rs"]);
+    console.tab_accept();
+    assert_eq!(console.input, "cat src/console.rs");
+}
+#[test]
+fn test_tab_bash() {
+    let mut console = completing_console(CompletionStyle::Bash);
+    console.input = "s".to_string();
+    console.cursor = 1;
+    // the first tab inserts what the candidates have in common
+    console.run_action(crate::Action::Complete);
+    assert_eq!(console.input, "st");
+    assert!(!console.tab_popup);
+    // the second lists them
+    console.cursor = 2;
+    console.run_action(crate::Action::Complete);
+    assert!(console.tab_popup);
+    assert_eq!(visible(&console), vec!["start", "stop", "status"]);
+    assert_eq!(console.input, "st");
+}
@@ -0,0 +1,296 @@
+use std::{
+    fmt::{self, Write},
+    sync::{Arc, RwLock},
+};
+
+use egui::Color32;
+use tracing::{
+    field::{Field, Visit},
+    level_filters::LevelFilter,
+    span, Event, Level, Metadata, Subscriber,
+};
+use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
+
+use crate::{filter, writer::ConsoleWriter, Span};
+
+// a default level plus per target levels, written like RUST_LOG: "warn,my_app=debug"
+#[derive(Debug, Clone, PartialEq)]
+struct Directives {
+    level: LevelFilter,
+    targets: Vec<(String, LevelFilter)>,
+}
+
+impl Directives {
+    fn parse(directive: &str) -> Result<Self, String> {
+        let mut directives = Directives {
+            level: LevelFilter::ERROR,
+            targets: Vec::new(),
+        };
+        for part in directive
+            .split(',')
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+        {
+            let parse_level = |level: &str| {
+                level
+                    .parse::<LevelFilter>()
+                    .map_err(|_| format!("unknown level '{}' in '{}'", level, part))
+            };
+            match part.split_once('=') {
+                Some((target, level)) => directives.set(target.trim(), parse_level(level.trim())?),
+                None => directives.level = parse_level(part)?,
+            }
+        }
+        Ok(directives)
+    }
+    fn set(&mut self, target: &str, level: LevelFilter) {
+        filter::set_level(&mut self.targets, target, level);
+    }
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        *metadata.level() <= filter::level_for(&self.targets, self.level, metadata.target())
+    }
+}
+
+impl fmt::Display for Directives {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.level)?;
+        for (target, level) in &self.targets {
+            write!(f, ",{}={}", target, level)?;
+        }
+        Ok(())
+    }
+}
+
+/// A handle for changing the [`ConsoleLayer`] filter while the app runs
+///
+/// Pass it to [`crate::ConsoleWindow::trace_command`] to let the user change it
+/// from the prompt with the built in `tracefilter` command.
+#[derive(Debug, Clone)]
+pub struct TraceHandle {
+    directives: Arc<RwLock<Directives>>,
+}
+
+impl TraceHandle {
+    /// The current filter, in the same form [`TraceHandle::set_directive`] takes
+    pub fn directive(&self) -> String {
+        self.directives.read().unwrap().to_string()
+    }
+    /// Replace the filter
+    /// # Arguments
+    /// * `directive` - a default level and `target=level` pairs, comma separated, eg `warn,my_app::net=trace`
+    ///
+    /// A bad directive is an error and leaves the filter as it was.
+    pub fn set_directive(&self, directive: &str) -> Result<(), String> {
+        *self.directives.write().unwrap() = Directives::parse(directive)?;
+        Ok(())
+    }
+
+    // the built in tracefilter command
+    // tracefilter              - show the filter
+    // tracefilter <directive>  - replace it
+    pub(crate) fn command(&self, args: &[&str]) -> Result<String, String> {
+        if !args.is_empty() {
+            self.set_directive(&args.join(""))?;
+        }
+        Ok(self.directive())
+    }
+}
+
+// the formatted fields of a span, kept in the span's extensions
+struct SpanFields(String);
+
+// formats fields as "message k=v k=v"
+#[derive(Default)]
+struct FieldFormatter {
+    message: String,
+    fields: String,
+}
+
+impl FieldFormatter {
+    fn push(&mut self, field: &Field, value: fmt::Arguments<'_>) {
+        if field.name() == "message" {
+            let _ = self.message.write_fmt(value);
+        } else {
+            if !self.fields.is_empty() {
+                self.fields.push(' ');
+            }
+            let _ = write!(self.fields, "{}={}", field.name(), value);
+        }
+    }
+}
+
+impl Visit for FieldFormatter {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.push(field, format_args!("{}", value));
+    }
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.push(field, format_args!("{:?}", value));
+    }
+}
+
+/// A [`tracing_subscriber::Layer`] that writes events to a [`crate::ConsoleWindow`]
+///
+/// Available with the `tracing` feature.
+///
+/// ```ignore
+/// let layer = ConsoleLayer::new(console.writer()).directive("info,my_app::net=debug")?;
+/// console.trace_command(layer.handle());
+/// tracing_subscriber::registry().with(layer).init();
+/// ```
+///
+/// Each event is written as its level, the spans it is in (with their fields), its target,
+/// message and fields. Errors, warnings and info events use the console theme's colours,
+/// debug is plain and trace is grey. The filter only applies to this layer, other layers
+/// still see everything.
+///
+/// Like [`crate::ConsoleLogger`] it does not ask egui for a repaint on the ui thread, events
+/// there show up the next time the console is drawn.
+#[derive(Debug)]
+pub struct ConsoleLayer {
+    writer: ConsoleWriter,
+    directives: Arc<RwLock<Directives>>,
+}
+
+impl ConsoleLayer {
+    /// Create a layer writing to the console, the default level is `INFO`
+    pub fn new(writer: ConsoleWriter) -> Self {
+        Self {
+            writer: writer.deferred(),
+            directives: Arc::new(RwLock::new(Directives {
+                level: LevelFilter::INFO,
+                targets: Vec::new(),
+            })),
+        }
+    }
+    /// Set the filter, see [`TraceHandle::set_directive`]
+    pub fn directive(self, directive: &str) -> Result<Self, String> {
+        self.handle().set_directive(directive)?;
+        Ok(self)
+    }
+    /// Get a handle for changing the filter later
+    pub fn handle(&self) -> TraceHandle {
+        TraceHandle {
+            directives: self.directives.clone(),
+        }
+    }
+    fn format_event<S>(event: &Event<'_>, ctx: &Context<'_, S>) -> String
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        let metadata = event.metadata();
+        let mut line = format!("{:<5} ", metadata.level());
+        if let Some(scope) = ctx.event_scope(event) {
+            let mut any = false;
+            for span in scope.from_root() {
+                let _ = write!(line, "{}", span.name());
+                if let Some(SpanFields(fields)) = span.extensions().get::<SpanFields>() {
+                    if !fields.is_empty() {
+                        let _ = write!(line, "{{{}}}", fields);
+                    }
+                }
+                line.push(':');
+                any = true;
+            }
+            if any {
+                line.push(' ');
+            }
+        }
+        let mut fields = FieldFormatter::default();
+        event.record(&mut fields);
+        let _ = write!(line, "{}: {}", metadata.target(), fields.message);
+        if !fields.fields.is_empty() {
+            if !fields.message.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&fields.fields);
+        }
+        line
+    }
+}
+
+impl<S> Layer<S> for ConsoleLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            let mut fields = FieldFormatter::default();
+            attrs.record(&mut fields);
+            span.extensions_mut().insert(SpanFields(fields.fields));
+        }
+    }
+
+    fn on_record(&self, id: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            let mut extensions = span.extensions_mut();
+            if let Some(SpanFields(fields)) = extensions.get_mut::<SpanFields>() {
+                let mut more = FieldFormatter {
+                    fields: std::mem::take(fields),
+                    ..Default::default()
+                };
+                values.record(&mut more);
+                *fields = more.fields;
+            }
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        if !self.directives.read().unwrap().enabled(event.metadata()) {
+            return;
+        }
+        let line = Self::format_event(event, &ctx);
+        match *event.metadata().level() {
+            Level::ERROR => self.writer.write_error(&line),
+            Level::WARN => self.writer.write_warning(&line),
+            Level::INFO => self.writer.write_info(&line),
+            Level::DEBUG => self.writer.write_styled(vec![Span::new(line)]),
+            Level::TRACE => self
+                .writer
+                .write_styled(vec![Span::new(line).color(Color32::GRAY)]),
+        }
+    }
+}
+
+#[test]
+fn test_trace_directives() {
+    let directives = Directives::parse("warn, app::net=trace,db=off").unwrap();
+    assert_eq!(directives.to_string(), "warn,app::net=trace,db=off");
+    assert_eq!(Directives::parse(&directives.to_string()), Ok(directives));
+    assert!(Directives::parse("app=loud").is_err());
+}
+
+#[test]
+fn test_trace_to_console() {
+    use tracing_subscriber::layer::SubscriberExt;
+
+    let mut console = crate::ConsoleBuilder::new().build();
+    let layer = ConsoleLayer::new(console.writer())
+        .directive("info,app::net=debug")
+        .unwrap();
+    let handle = layer.handle();
+    let subscriber = tracing_subscriber::registry().with(layer);
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!(target: "app", "conn", id = 3);
+        let _enter = span.enter();
+        tracing::info!(target: "app", bytes = 10, "sent");
+        tracing::debug!(target: "app", "hidden");
+        tracing::debug!(target: "app::net", "shown");
+        handle.command(&["off"]).unwrap();
+        tracing::error!(target: "app", "hidden");
+    });
+    assert!(handle.command(&["app=loud"]).is_err());
+    assert_eq!(handle.directive(), "off");
+    console.drain_writers();
+    let lines: Vec<_> = console
+        .scrollback
+        .range(0..console.scrollback.len())
+        .map(|l| l.text.clone())
+        .collect();
+    assert_eq!(
+        lines,
+        vec![
+            "INFO  conn{id=3}: app: sent bytes=10",
+            "DEBUG conn{id=3}: app::net: shown"
+        ]
+    );
+}
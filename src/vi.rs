@@ -0,0 +1,302 @@
+use egui::{Key, Modifiers};
+
+use crate::{console::EditMode, ConsoleWindow};
+
+// what kind of character, vi words are runs of the same kind
+#[derive(PartialEq)]
+enum CharClass {
+    Space,
+    Word,
+    Punctuation,
+}
+
+fn class(c: char) -> CharClass {
+    if c.is_whitespace() {
+        CharClass::Space
+    } else if c.is_alphanumeric() || c == '_' {
+        CharClass::Word
+    } else {
+        CharClass::Punctuation
+    }
+}
+
+// vi editing of the input line. In insert mode keys behave as usual, escape goes to normal
+// mode where typed characters are commands
+impl ConsoleWindow {
+    pub(crate) fn vi_normal(&self) -> bool {
+        self.edit_mode == EditMode::Vi && self.vi_normal
+    }
+    // the mode indicator shown in front of the prompt
+    pub(crate) fn vi_indicator(&self) -> &'static str {
+        match (self.edit_mode, self.vi_normal) {
+            (EditMode::Emacs, _) => "",
+            (EditMode::Vi, true) => "(cmd)",
+            (EditMode::Vi, false) => "(ins)",
+        }
+    }
+    // keys that mean something different in vi mode, None lets the key through as usual
+    pub(crate) fn vi_key(&mut self, key: &Key, modifiers: Modifiers) -> Option<bool> {
+        if modifiers != Modifiers::NONE && !(modifiers.ctrl && *key == Key::R) {
+            return None;
+        }
+        match (self.vi_normal, key) {
+            (false, Key::Escape) => {
+                self.vi_normal = true;
+                self.vi_pending = None;
+                // the cursor goes back onto the last character typed
+                let cursor = self.prev_char(self.cursor.min(self.input.len()));
+                self.move_to(cursor);
+                Some(true)
+            }
+            (true, Key::Escape) => {
+                self.vi_pending = None;
+                Some(true)
+            }
+            (true, Key::R) if modifiers.ctrl => {
+                self.redo();
+                Some(true)
+            }
+            (true, Key::Backspace) => {
+                self.vi_command('h');
+                Some(true)
+            }
+            (true, Key::Delete) => {
+                self.vi_command('x');
+                Some(true)
+            }
+            _ => None,
+        }
+    }
+    // characters typed in normal mode, anything after a command that enters insert mode is inserted
+    pub(crate) fn vi_type(&mut self, text: &str) {
+        for (i, c) in text.char_indices() {
+            if !self.vi_normal {
+                let cursor = self.cursor.min(self.input.len());
+                self.input.insert_str(cursor, &text[i..]);
+                self.move_to(cursor + text.len() - i);
+                return;
+            }
+            self.vi_command(c);
+        }
+    }
+    pub(crate) fn vi_command(&mut self, c: char) {
+        let cursor = self.cursor.min(self.input.len());
+        if let Some(op) = self.vi_pending.take() {
+            // dd cc yy work on the whole line
+            let range = if c == op {
+                Some((0, self.input.len()))
+            } else {
+                // cw changes to the end of the word, like ce
+                let motion = if op == 'c' && c == 'w' { 'e' } else { c };
+                self.vi_motion(motion, true).map(|target| {
+                    if target < cursor {
+                        (target, cursor)
+                    } else {
+                        (cursor, target)
+                    }
+                })
+            };
+            if let Some((start, end)) = range {
+                self.vi_operate(op, start, end);
+            }
+            return;
+        }
+        if let Some(target) = self.vi_motion(c, false) {
+            self.move_to(target);
+            return;
+        }
+        match c {
+            'i' => self.vi_insert(cursor),
+            'a' => self.vi_insert(self.next_char(cursor)),
+            'I' => self.vi_insert(0),
+            'A' => self.vi_insert(self.input.len()),
+            'd' | 'c' | 'y' => self.vi_pending = Some(c),
+            'D' => self.vi_operate('d', cursor, self.input.len()),
+            'C' => self.vi_operate('c', cursor, self.input.len()),
+            'x' => self.vi_operate('d', cursor, self.next_char(cursor)),
+            'p' | 'P' => {
+                if let Some(text) = self.kill_ring.last().cloned() {
+                    self.vi_save_undo();
+                    let at = if c == 'p' {
+                        self.next_char(cursor)
+                    } else {
+                        cursor
+                    };
+                    self.input.insert_str(at, &text);
+                    let end = self.prev_char(at + text.len());
+                    self.move_to(end);
+                }
+            }
+            'u' => self.undo(),
+            '/' | '?' => self.enter_search_mode(),
+            _ => {}
+        }
+    }
+    // where a motion goes. As the target of an operator it can be the end of the line, and `e` and `$`
+    // include the character they land on
+    fn vi_motion(&self, c: char, for_operator: bool) -> Option<usize> {
+        let cursor = self.cursor.min(self.input.len());
+        let text = &self.input;
+        let at = |pos: usize| text[pos..].chars().next();
+        let target = match c {
+            'h' => self.prev_char(cursor),
+            'l' => self.next_char(cursor),
+            '0' => 0,
+            '$' => text.len(),
+            'w' => {
+                let mut pos = cursor;
+                if let Some(first) = at(pos).map(class) {
+                    while at(pos).is_some_and(|c| class(c) == first && first != CharClass::Space) {
+                        pos = self.next_char(pos);
+                    }
+                }
+                while at(pos).is_some_and(char::is_whitespace) {
+                    pos = self.next_char(pos);
+                }
+                pos
+            }
+            'b' => {
+                let mut pos = cursor;
+                while pos > 0 && text[..pos].ends_with(char::is_whitespace) {
+                    pos = self.prev_char(pos);
+                }
+                if let Some(kind) = text[..pos].chars().next_back().map(class) {
+                    while text[..pos]
+                        .chars()
+                        .next_back()
+                        .is_some_and(|c| class(c) == kind)
+                    {
+                        pos = self.prev_char(pos);
+                    }
+                }
+                pos
+            }
+            'e' => {
+                // past the end of the current word, so it moves on to the next one
+                let mut pos = self.next_char(cursor);
+                while at(pos).is_some_and(char::is_whitespace) {
+                    pos = self.next_char(pos);
+                }
+                if let Some(kind) = at(pos).map(class) {
+                    while at(self.next_char(pos)).is_some_and(|c| class(c) == kind) {
+                        pos = self.next_char(pos);
+                    }
+                }
+                if for_operator {
+                    self.next_char(pos)
+                } else {
+                    pos
+                }
+            }
+            _ => return None,
+        };
+        // outside an operator the cursor sits on a character, not after the last one
+        if !for_operator && target >= text.len() {
+            return Some(self.prev_char(text.len()));
+        }
+        Some(target)
+    }
+    fn vi_operate(&mut self, op: char, start: usize, end: usize) {
+        if start == end {
+            return;
+        }
+        match op {
+            'y' => {
+                self.kill_ring_push(self.input[start..end].to_string());
+                self.move_to(start);
+            }
+            _ => {
+                self.vi_save_undo();
+                self.kill(start..end);
+                if op == 'c' {
+                    self.vi_normal = false;
+                } else {
+                    let cursor = self.cursor.min(self.prev_char(self.input.len()));
+                    self.move_to(cursor);
+                }
+            }
+        }
+    }
+    fn vi_insert(&mut self, pos: usize) {
+        self.vi_save_undo();
+        self.vi_normal = false;
+        self.move_to(pos);
+    }
+    // changes from normal mode are undo steps of their own
+    fn vi_save_undo(&mut self) {
+        self.undo_record(true);
+    }
+    fn prev_char(&self, pos: usize) -> usize {
+        self.input[..pos]
+            .chars()
+            .next_back()
+            .map_or(0, |c| pos - c.len_utf8())
+    }
+    fn next_char(&self, pos: usize) -> usize {
+        self.input[pos..]
+            .chars()
+            .next()
+            .map_or(pos, |c| pos + c.len_utf8())
+    }
+}
+
+#[test]
+fn test_vi_mode() {
+    let mut console = crate::ConsoleBuilder::new().edit_mode(EditMode::Vi).build();
+    console.input = "git commit -m fix".to_string();
+    console.cursor = console.input.len();
+    assert_eq!(console.vi_key(&Key::Escape, Modifiers::NONE), Some(true));
+    assert_eq!(console.vi_indicator(), "(cmd)");
+    assert_eq!(console.cursor, 16);
+    console.vi_type("0w");
+    assert_eq!(console.cursor, 4);
+    console.vi_type("e");
+    assert_eq!(console.cursor, 9);
+    console.vi_type("bdw");
+    assert_eq!(console.input, "git -m fix");
+    console.vi_type("u");
+    assert_eq!(console.input, "git commit -m fix");
+    console.vi_key(&Key::R, Modifiers::CTRL);
+    assert_eq!(console.input, "git -m fix");
+    console.vi_type("$x");
+    assert_eq!(console.input, "git -m fi");
+    console.vi_type("0P");
+    assert_eq!(console.input, "xgit -m fi");
+    console.vi_type("lcwpush");
+    assert_eq!(console.input, "xpush -m fi");
+    assert_eq!(console.vi_indicator(), "(ins)");
+    console.vi_key(&Key::Escape, Modifiers::NONE);
+    console.vi_type("0yyd$");
+    assert_eq!(console.input, "");
+    console.vi_type("pAok");
+    assert_eq!(console.input, "xpush -m fiok");
+    console.vi_key(&Key::Escape, Modifiers::NONE);
+    for _ in 0..20 {
+        console.vi_type("0yw");
+    }
+    assert_eq!(console.kill_ring.len(), 16);
+}
+#[test]
+fn test_vi_paste() {
+    let mut console = crate::ConsoleBuilder::new().edit_mode(EditMode::Vi).build();
+    let ctx = egui::Context::default();
+    let frame = |console: &mut ConsoleWindow, events: Vec<egui::Event>| {
+        let input = egui::RawInput {
+            events,
+            ..Default::default()
+        };
+        let _ = ctx.run(input, |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                console.draw(ui);
+            });
+            ctx.memory_mut(|mem| mem.request_focus(console.id));
+        });
+    };
+    frame(&mut console, Vec::new());
+    frame(&mut console, vec![egui::Event::Paste("ls".to_string())]);
+    assert_eq!(console.input, "ls");
+    console.vi_key(&Key::Escape, Modifiers::NONE);
+    // normal mode keys are commands, a paste is not typed
+    frame(&mut console, vec![egui::Event::Paste("dd".to_string())]);
+    assert_eq!(console.input, "ls");
+}
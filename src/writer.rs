@@ -0,0 +1,177 @@
+use std::{
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, OnceLock,
+    },
+    thread::{self, ThreadId},
+};
+
+use egui::Context;
+
+use crate::style::Span;
+
+// what a writer sends to the console
+#[derive(Debug)]
+pub(crate) enum Output {
+    Text(String),
+    Styled(Vec<Span>),
+    Error(String),
+    Warning(String),
+    Info(String),
+}
+
+// the console end of the writers' channel
+#[derive(Debug)]
+pub(crate) struct WriterChannel {
+    pub(crate) sender: Sender<Output>,
+    pub(crate) receiver: Receiver<Output>,
+    // filled in the first time the console is drawn
+    pub(crate) ui: Arc<OnceLock<UiHandle>>,
+}
+
+// what writers need to wake up the console
+#[derive(Debug)]
+pub(crate) struct UiHandle {
+    pub(crate) ctx: Context,
+    // the thread the console is drawn on
+    pub(crate) thread: ThreadId,
+}
+
+impl Default for WriterChannel {
+    fn default() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            sender,
+            receiver,
+            ui: Arc::new(OnceLock::new()),
+        }
+    }
+}
+
+/// A handle for writing to a [`crate::ConsoleWindow`] from anywhere, including other threads
+///
+/// Get one from [`crate::ConsoleWindow::writer`]. It is cheap to clone and
+/// can be sent to other threads. Output is queued and shows up the next time the
+/// console is drawn, a repaint is requested so that happens without waiting for user input.
+/// Output written before the console is first drawn is kept.
+///
+/// It also implements [`std::io::Write`] and [`std::fmt::Write`] so it can be handed to code
+/// that writes to a stream. Stream output is sent a line at a time, anything
+/// left over is sent on `flush` or when the writer is dropped.
+///
+/// ```ignore
+/// let writer = console.writer();
+/// std::thread::spawn(move || {
+///     writer.write("working...");
+///     writer.write_info("done");
+/// });
+/// ```
+#[derive(Debug)]
+pub struct ConsoleWriter {
+    sender: Sender<Output>,
+    ui: Arc<OnceLock<UiHandle>>,
+    // false for writers called from log and tracing callbacks, see `ConsoleWriter::deferred`
+    repaint_on_ui_thread: bool,
+    // partial line from io::Write / fmt::Write, not shared between clones
+    partial: Vec<u8>,
+}
+
+impl Clone for ConsoleWriter {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+            ui: self.ui.clone(),
+            repaint_on_ui_thread: self.repaint_on_ui_thread,
+            partial: Vec::new(),
+        }
+    }
+}
+
+impl ConsoleWriter {
+    pub(crate) fn new(channel: &WriterChannel) -> Self {
+        Self {
+            sender: channel.sender.clone(),
+            ui: channel.ui.clone(),
+            repaint_on_ui_thread: true,
+            partial: Vec::new(),
+        }
+    }
+    // a writer that never asks for a repaint from the ui thread, for the log and tracing bridges.
+    // egui logs while it holds its context lock, so asking it for a repaint then would deadlock.
+    // What they write on the ui thread shows up the next time the console is drawn.
+    #[cfg(any(feature = "log", feature = "tracing"))]
+    pub(crate) fn deferred(mut self) -> Self {
+        self.repaint_on_ui_thread = false;
+        self
+    }
+    fn send(&self, output: Output) {
+        // an error means the console has gone, nothing to do about that
+        if self.sender.send(output).is_ok() {
+            if let Some(ui) = self.ui.get() {
+                if self.repaint_on_ui_thread || thread::current().id() != ui.thread {
+                    ui.ctx.request_repaint();
+                }
+            }
+        }
+    }
+    /// Write a line to the console, see [`crate::ConsoleWindow::write`]
+    pub fn write(&self, data: &str) {
+        self.send(Output::Text(data.to_string()));
+    }
+    /// Write a line made of styled spans, see [`crate::ConsoleWindow::write_styled`]
+    pub fn write_styled(&self, spans: Vec<Span>) {
+        self.send(Output::Styled(spans));
+    }
+    /// Write a line in the theme's error style
+    pub fn write_error(&self, data: &str) {
+        self.send(Output::Error(data.to_string()));
+    }
+    /// Write a line in the theme's warning style
+    pub fn write_warning(&self, data: &str) {
+        self.send(Output::Warning(data.to_string()));
+    }
+    /// Write a line in the theme's info style
+    pub fn write_info(&self, data: &str) {
+        self.send(Output::Info(data.to_string()));
+    }
+
+    // send all the complete lines in the partial buffer
+    fn send_lines(&mut self) {
+        if let Some(end) = self.partial.iter().rposition(|&b| b == b'\n') {
+            let lines: Vec<u8> = self.partial.drain(..=end).collect();
+            self.write(&String::from_utf8_lossy(&lines));
+        }
+    }
+    fn send_partial(&mut self) {
+        if !self.partial.is_empty() {
+            let rest = std::mem::take(&mut self.partial);
+            self.write(&String::from_utf8_lossy(&rest));
+        }
+    }
+}
+
+impl std::io::Write for ConsoleWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.partial.extend_from_slice(buf);
+        self.send_lines();
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.send_partial();
+        Ok(())
+    }
+}
+
+impl std::fmt::Write for ConsoleWriter {
+    fn write_str(&mut self, s: &str) -> std::fmt::Result {
+        self.partial.extend_from_slice(s.as_bytes());
+        self.send_lines();
+        Ok(())
+    }
+}
+
+impl Drop for ConsoleWriter {
+    fn drop(&mut self) {
+        self.send_partial();
+    }
+}